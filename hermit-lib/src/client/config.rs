@@ -1,4 +1,22 @@
-#[derive(Debug, Default)]
+use super::progress::ProgressObserver;
+use crate::crypto::secrets::KeyUpdateLimits;
+use crate::crypto::CipherSuite;
+
 pub(super) struct Config {
     pub(super) requested_len_limit: Option<usize>,
-}
\ No newline at end of file
+    pub(super) progress_observer: Option<ProgressObserver>,
+    // The cipher suites offered in the handshake, in order of preference.
+    pub(super) cipher_suites: Vec<CipherSuite>,
+    pub(super) key_update_limits: KeyUpdateLimits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            requested_len_limit: None,
+            progress_observer: None,
+            cipher_suites: CipherSuite::SUPPORTED.to_vec(),
+            key_update_limits: KeyUpdateLimits::default(),
+        }
+    }
+}
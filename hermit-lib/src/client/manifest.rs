@@ -0,0 +1,110 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_std::fs;
+use async_std::prelude::*;
+
+use crate::error::{Error, TransferError};
+use crate::proto::message::transfer::{Resource, RESOURCE_PATH_SEPARATOR};
+
+// List a local file, or a directory recursively, as resources to be sent together with
+// their local paths. The resources are named after the given path, such that a directory
+// is rebuilt under its own name by the receiver. Directories are listed before their
+// contents, which are sorted by name.
+// NOTE: Symbolic links within a directory are not followed, and are rejected like any other
+// entry that is neither a file nor a directory.
+pub async fn local_resources(path: impl AsRef<Path>) -> Result<Vec<(Resource, PathBuf)>, Error> {
+    let path = path.as_ref();
+    let canonical_path = fs::canonicalize(path).await?;
+    let name = canonical_path
+        .file_name()
+        .ok_or_else(|| TransferError::InvalidResourcePath(path.display().to_string()))?;
+
+    let mut resources = Vec::new();
+    // The entries still to be listed, in reverse order.
+    let mut pending = vec![(
+        join_resource_path(None, name)?,
+        path.to_path_buf(),
+        fs::metadata(path).await?,
+    )];
+    while let Some((resource_path, local_path, metadata)) = pending.pop() {
+        if metadata.is_file() {
+            resources.push((Resource::file(metadata.len(), resource_path), local_path));
+            continue;
+        }
+        if !metadata.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is neither a file nor a directory", local_path.display()),
+            )
+            .into());
+        }
+
+        let mut entries = fs::read_dir(&local_path).await?;
+        let mut children = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            children.push((
+                join_resource_path(Some(&resource_path), &entry.file_name())?,
+                entry.path().into(),
+                entry.metadata().await?,
+            ));
+        }
+        children.sort_by(|(a, _, _), (b, _, _)| b.cmp(a));
+        pending.extend(children);
+
+        resources.push((Resource::directory(resource_path), local_path));
+    }
+
+    Ok(resources)
+}
+
+fn join_resource_path(parent: Option<&str>, name: &std::ffi::OsStr) -> Result<String, Error> {
+    let name = name
+        .to_str()
+        .ok_or_else(|| TransferError::InvalidResourcePath(name.to_string_lossy().into_owned()))?;
+    Ok(match parent {
+        Some(parent) => format!("{}{}{}", parent, RESOURCE_PATH_SEPARATOR, name),
+        None => name.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_local_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("b").join("empty"))
+            .await
+            .unwrap();
+        fs::write(root.join("a.txt"), b"hello").await.unwrap();
+        fs::write(root.join("b").join("c.txt"), b"").await.unwrap();
+
+        let resources = local_resources(&root).await.unwrap();
+        assert_eq!(
+            resources,
+            vec![
+                (Resource::directory("root"), root.clone()),
+                (Resource::file(5, "root/a.txt"), root.join("a.txt")),
+                (Resource::directory("root/b"), root.join("b")),
+                (
+                    Resource::file(0, "root/b/c.txt"),
+                    root.join("b").join("c.txt")
+                ),
+                (
+                    Resource::directory("root/b/empty"),
+                    root.join("b").join("empty")
+                ),
+            ]
+        );
+
+        let resources = local_resources(root.join("a.txt")).await.unwrap();
+        assert_eq!(
+            resources,
+            vec![(Resource::file(5, "a.txt"), root.join("a.txt"))]
+        );
+    }
+}
@@ -1,31 +1,50 @@
 mod config;
+mod manifest;
+mod progress;
 mod server;
 mod state;
 
+use async_std::io::{self, Read, Write};
+use ring::signature::Ed25519KeyPair;
+
+use crate::crypto::secrets::KeyUpdateLimits;
+use crate::crypto::CipherSuite;
 use crate::error::Error;
-use crate::proto::message::{handshake, len_limit};
+use crate::proto::message::{
+    auth, handshake, len_limit, recv_header, transfer, Message, PlainMessageType, Secure,
+    SecureMessageType,
+};
 use crate::proto::stream::{BaseStream, Plain, PlainStream};
-use crate::proto::Side;
+use crate::proto::{self, ProtocolVersion, Side};
+use crate::state::{PlainState, SecureState, State};
 use crate::{crypto, error};
 
 use self::config::Config;
-use self::server::ServerSigPubKey;
+use self::progress::ProgressTracker;
 use self::state::*;
 
+pub use self::manifest::local_resources;
+pub use self::progress::Progress;
+pub use self::server::ServerSigPubKey;
+pub use self::state::{
+    HandshakingConnection, InsecureConnection, NoConnection, ReceiveResourceRequested,
+    SendResourceRequested, UpgradedConnection,
+};
+
 pub struct Client<T: State> {
     state: T,
     conf: Config,
 }
 
 impl Client<NoConnection> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             state: NoConnection,
             conf: Config::default(),
         }
     }
 
-    fn connect(self, stream: BaseStream) -> Client<InsecureConnection> {
+    pub fn connect(self, stream: BaseStream) -> Client<InsecureConnection> {
         Client {
             state: InsecureConnection::new(PlainStream::from(stream)),
             conf: self.conf,
@@ -33,8 +52,87 @@ impl Client<NoConnection> {
     }
 }
 
+impl Default for Client<NoConnection> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Client<InsecureConnection> {
-    async fn client_hello(mut self) -> Result<Client<HandshakingConnection>, (Self, Error)> {
+    // Perform the whole handshake, verifying the server with its signature public key.
+    pub async fn handshake(
+        self,
+        server_sig_pub_key: ServerSigPubKey,
+    ) -> Result<Client<UpgradedConnection>, (Client<InsecureConnection>, Error)> {
+        let (client, server_hello_msg) = self.recv_server_hello().await?;
+        client
+            .server_hello(server_hello_msg, server_sig_pub_key)
+            .await
+    }
+
+    // Perform the whole handshake, trusting the signature public key presented by the server,
+    // which is returned such that it can be recorded and verified on later connections.
+    // NOTE: This offers no protection against an active attacker on the first connection.
+    pub async fn handshake_trust_on_first_use(
+        self,
+    ) -> Result<
+        (
+            Client<UpgradedConnection>,
+            [u8; crypto::ED25519_PUBLIC_KEY_LEN],
+        ),
+        (Client<InsecureConnection>, Error),
+    > {
+        let (client, server_hello_msg) = self.recv_server_hello().await?;
+        let sig_public_key_bytes = server_hello_msg.sig_public_key_bytes;
+        let client = client
+            .server_hello(
+                server_hello_msg,
+                ServerSigPubKey::from(sig_public_key_bytes),
+            )
+            .await?;
+
+        Ok((client, sig_public_key_bytes))
+    }
+
+    async fn recv_server_hello(
+        self,
+    ) -> Result<
+        (Client<HandshakingConnection>, handshake::ServerHelloMessage),
+        (Client<InsecureConnection>, Error),
+    > {
+        let mut client = self.client_hello().await?;
+
+        let server_hello_msg = match client.recv().await {
+            Ok(msg) if msg.plain_msg_type() == PlainMessageType::HandshakeFailure => {
+                // NOTE: The server aborts the handshake when it has no version or cipher suite
+                // in common with the client.
+                Err(
+                    match handshake::HandshakeFailureMessage::try_from(msg)
+                        .and_then(handshake::HandshakeFailure::try_from)
+                    {
+                        Ok(failure) => failure.into(),
+                        Err(error) => error.into(),
+                    },
+                )
+            }
+            Ok(msg) => handshake::ServerHelloMessage::try_from(msg).map_err(Error::from),
+            Err(error) => Err(error),
+        };
+        match server_hello_msg {
+            Ok(server_hello_msg) => Ok((client, server_hello_msg)),
+            Err(error) => Err((
+                Client {
+                    state: client.state.failed(),
+                    conf: client.conf,
+                },
+                error,
+            )),
+        }
+    }
+
+    pub(crate) async fn client_hello(
+        mut self,
+    ) -> Result<Client<HandshakingConnection>, (Self, Error)> {
         let client_hello_result = async {
             // Generate client nonce
             let client_nonce = crypto::generate_nonce().await?;
@@ -49,6 +147,8 @@ impl Client<InsecureConnection> {
                     public_key.as_ref(),
                 )
                 .unwrap(),
+                versions: proto::encode_versions(proto::SUPPORTED_PROTOCOL_VERSIONS),
+                suites: crypto::suite::encode(&self.conf.cipher_suites),
             };
 
             self.state
@@ -56,7 +156,7 @@ impl Client<InsecureConnection> {
                 .send(client_hello_msg.into())
                 .await?;
             Ok::<HandshakeContext, Error>(HandshakeContext {
-                nonce: client_nonce,
+                client_hello_msg,
                 private_key: client_private_key,
             })
         }
@@ -73,7 +173,7 @@ impl Client<InsecureConnection> {
 }
 
 impl Client<HandshakingConnection> {
-    async fn server_hello(
+    pub(crate) async fn server_hello(
         mut self,
         server_hello_msg: handshake::ServerHelloMessage,
         server_sig_pub_key: ServerSigPubKey,
@@ -81,22 +181,37 @@ impl Client<HandshakingConnection> {
         let server_hello_result = async {
             // SAFETY: private key has not been taken out before
             let HandshakeContext {
-                nonce: client_nonce,
+                client_hello_msg,
                 private_key: client_private_key,
             } = self.state.context().unwrap();
 
             // Verify
             let (server_public_key, nonces) = crypto::verify_server_hello(
                 server_hello_msg,
-                client_nonce,
+                &client_hello_msg,
                 server_sig_pub_key.as_ref(),
             )?;
 
+            // The version and cipher suite picked must be among those offered.
+            let version = ProtocolVersion::try_from(server_hello_msg.version[0])
+                .map_err(error::InvalidMessageError::from)?;
+            if !client_hello_msg.versions.contains(&version.into()) {
+                return Err(error::InvalidMessageError::UnexpectedProtocolVersion(version).into());
+            }
+            let suite = CipherSuite::try_from(server_hello_msg.suite[0])
+                .map_err(error::InvalidMessageError::from)?;
+            if !self.conf.cipher_suites.contains(&suite) {
+                return Err(error::InvalidMessageError::UnexpectedCipherSuite(suite).into());
+            }
+            self.state.plain_stream().set_version(version);
+
             // Generate session secrets
             let session_secrets = crypto::generate_session_secrets(
                 client_private_key,
                 server_public_key,
                 nonces,
+                suite,
+                crypto::handshake_transcript(&client_hello_msg, &server_hello_msg),
                 Side::Client,
             )
             .await?;
@@ -106,10 +221,16 @@ impl Client<HandshakingConnection> {
         .await;
 
         match server_hello_result {
-            Ok(session_secrets) => Ok(Client {
-                state: UpgradedConnection::new(self.state, session_secrets),
-                conf: self.conf,
-            }),
+            Ok(session_secrets) => {
+                let mut state = UpgradedConnection::new(self.state, session_secrets);
+                state
+                    .secure_stream()
+                    .set_key_update_limits(self.conf.key_update_limits);
+                Ok(Client {
+                    state,
+                    conf: self.conf,
+                })
+            }
             Err(error) => Err((
                 Client {
                     state: self.state.failed(),
@@ -122,12 +243,340 @@ impl Client<HandshakingConnection> {
 }
 
 impl Client<UpgradedConnection> {
-    async fn send_resource_request(mut self) -> Result<Client<SendResourceRequested>, Error> {
-        todo!()
+    // Prove the client's identity to the server by signing the handshake transcript with its
+    // signature key pair, which the server checks against its allowlist.
+    pub async fn authenticate(
+        mut self,
+        client_key_pair: &Ed25519KeyPair,
+    ) -> Result<Self, (Self, Error)> {
+        let authenticate_result = async {
+            let secure_stream = self.state.secure_stream();
+            auth::ClientAuthRequest {
+                public_key: crypto::identity::export_public_key(client_key_pair),
+                signature: crypto::sign_client_auth(
+                    secure_stream.session_secrets(),
+                    client_key_pair,
+                ),
+            }
+            .send(secure_stream)
+            .await?;
+
+            match auth::ClientAuthResponse::recv(secure_stream).await? {
+                auth::ClientAuthResponse::Ok => Ok(()),
+                auth::ClientAuthResponse::BadSignature => {
+                    Err(error::ClientAuthError::BadSignature.into())
+                }
+                auth::ClientAuthResponse::NotAllowed => {
+                    Err(error::ClientAuthError::NotAllowed.into())
+                }
+            }
+        }
+        .await;
+
+        match authenticate_result {
+            Ok(()) => Ok(self),
+            Err(error) => Err((self, error)),
+        }
+    }
+
+    pub async fn send_resource_request(
+        mut self,
+        request: transfer::SendResourceRequest,
+    ) -> Result<Client<SendResourceRequested>, (Self, Error)> {
+        let send_resource_result = async {
+            transfer::validate_manifest(&request.resources)?;
+            request.send(self.state.secure_stream()).await?;
+
+            match transfer::SendResourceResponse::recv(self.state.secure_stream()).await? {
+                transfer::SendResourceResponse::Ok {
+                    id,
+                    expiry,
+                    compression,
+                } => {
+                    id.validate()?;
+                    check_compression(&request.compression, compression)?;
+                    self.state.secure_stream().set_compression(compression);
+                    Ok::<SendResourceContext, Error>(SendResourceContext {
+                        progress: ProgressTracker::new(&request.resources),
+                        resources: request.resources,
+                        id,
+                        expiry,
+                        sent: 0,
+                    })
+                }
+                transfer::SendResourceResponse::InvalidReceiverControl => {
+                    Err(error::ResourceRequestError::InvalidReceiverControl.into())
+                }
+                transfer::SendResourceResponse::InvalidExpiry => {
+                    Err(error::ResourceRequestError::InvalidExpiry.into())
+                }
+                transfer::SendResourceResponse::ResourceTooLarge => {
+                    Err(error::ResourceRequestError::ResourceTooLarge.into())
+                }
+                transfer::SendResourceResponse::InvalidManifest => {
+                    Err(error::ResourceRequestError::InvalidManifest.into())
+                }
+                transfer::SendResourceResponse::Unauthorized => {
+                    Err(error::ResourceRequestError::Unauthorized.into())
+                }
+            }
+        }
+        .await;
+
+        match send_resource_result {
+            Ok(send_resource_context) => Ok(Client {
+                state: SendResourceRequested::new(self.state, send_resource_context),
+                conf: self.conf,
+            }),
+            Err(error) => Err((self, error)),
+        }
+    }
+
+    // NOTE: The receiver key pair is used to answer the challenge for a resource locked
+    // with `ReceiverControl::PublicKey`.
+    pub async fn receive_resource_request(
+        mut self,
+        request: transfer::ReceiveResourceRequest,
+        receiver_key_pair: Option<&Ed25519KeyPair>,
+    ) -> Result<Client<ReceiveResourceRequested>, (Self, Error)> {
+        let receive_resource_result = async {
+            let secure_stream = self.state.secure_stream();
+            request.send(secure_stream).await?;
+
+            let response = match recv_header(secure_stream).await? {
+                SecureMessageType::ReceiverChallenge => {
+                    let transfer::ReceiverChallenge { challenge } =
+                        transfer::ReceiverChallenge::recv_body(secure_stream).await?;
+
+                    // NOTE: Without a key pair, the challenge is still answered with an invalid
+                    // signature, such that the server rejects the request and the connection
+                    // stays usable.
+                    let signature = match receiver_key_pair {
+                        Some(receiver_key_pair) => crypto::sign_receiver_challenge(
+                            secure_stream.session_secrets(),
+                            &request.id.0,
+                            &challenge,
+                            receiver_key_pair,
+                        ),
+                        None => [0; crypto::ED25519_SIGNATURE_LEN],
+                    };
+                    transfer::ReceiverChallengeResponse { signature }
+                        .send(secure_stream)
+                        .await?;
+                    let response = transfer::ReceiveResourceResponse::recv(secure_stream).await?;
+                    if receiver_key_pair.is_none() {
+                        return Err(error::ResourceRequestError::MissingReceiverKeyPair.into());
+                    }
+                    response
+                }
+                SecureMessageType::ReceiveResourceResponse => {
+                    transfer::ReceiveResourceResponse::recv_body(secure_stream).await?
+                }
+                others => {
+                    return Err(
+                        error::InvalidMessageError::UnexpectedSecureMessageType(others).into(),
+                    )
+                }
+            };
+
+            match response {
+                transfer::ReceiveResourceResponse::Ok {
+                    resources,
+                    digests,
+                    expiry,
+                    compression,
+                } => {
+                    // NOTE: The manifest comes from the server, so it is checked before the
+                    // receiver writes anything to disk.
+                    transfer::validate_manifest(&resources)?;
+                    if !transfer::valid_offsets(&resources, &request.offsets) {
+                        return Err(error::ResourceRequestError::InvalidOffsets.into());
+                    }
+                    check_compression(&request.compression, compression)?;
+                    secure_stream.set_compression(compression);
+
+                    let offsets = match request.offsets.is_empty() {
+                        true => vec![0; resources.len()],
+                        false => request.offsets.clone(),
+                    };
+                    Ok::<ReceiveResourceContext, Error>(ReceiveResourceContext {
+                        progress: ProgressTracker::new(&resources),
+                        resources,
+                        expiry,
+                        received: 0,
+                        offsets,
+                        digests,
+                    })
+                }
+                transfer::ReceiveResourceResponse::Failed => {
+                    Err(error::ResourceRequestError::Failed.into())
+                }
+                transfer::ReceiveResourceResponse::InvalidOffsets => {
+                    Err(error::ResourceRequestError::InvalidOffsets.into())
+                }
+            }
+        }
+        .await;
+
+        match receive_resource_result {
+            Ok(receive_resource_context) => Ok(Client {
+                state: ReceiveResourceRequested::new(self.state, receive_resource_context),
+                conf: self.conf,
+            }),
+            Err(error) => Err((self, error)),
+        }
+    }
+
+    // Request the server to adjust the message length limit in between resource requests,
+    // returning whether it has been accepted.
+    pub async fn adjust_len_limit(&mut self, len_limit: usize) -> Result<bool, Error> {
+        self.request_len_limit(len_limit).await?;
+
+        let msg = self.recv().await?;
+        if msg.plain_msg_type() != PlainMessageType::AdjustLenLimitResponse {
+            return Err(
+                error::InvalidMessageError::UnexpectedMessageType(msg.plain_msg_type()).into(),
+            );
+        }
+        let response = len_limit::AdjustLenLimitResponse::try_from(msg)?;
+        self.request_len_limit_responded(response).await
+    }
+}
+
+impl Client<SendResourceRequested> {
+    pub fn resource_id(&self) -> &transfer::ResourceId {
+        &self.state.context().id
+    }
+
+    pub fn expiry(&self) -> chrono::DateTime<chrono::Utc> {
+        self.state.context().expiry
+    }
+
+    // Send the next file in the declared order, skipping the directories.
+    pub async fn send_resource(&mut self, reader: impl Read + Unpin) -> Result<(), Error> {
+        let (secure_stream, context) = self.state.parts_mut();
+        context.sent = next_file(&context.resources, context.sent);
+        let resource = context
+            .resources
+            .get(context.sent)
+            .ok_or(error::TransferError::NoRemainingResource)?;
+        let size = resource.size;
+        let mut progress = context.progress.progress(context.sent, resource, 0);
+        context.sent += 1;
+
+        let tracker = &context.progress;
+        let observer = &mut self.conf.progress_observer;
+        transfer::send_resource(secure_stream, size, reader, None, |len| {
+            tracker.advance(&mut progress, len);
+            if let Some(observer) = observer {
+                observer(&progress);
+            }
+        })
+        .await?;
+        context.progress.complete(size);
+
+        Ok(())
+    }
+
+    pub async fn send_completed(mut self) -> Result<Client<UpgradedConnection>, (Self, Error)> {
+        let context = self.state.context();
+        let remaining = remaining_files(&context.resources, context.sent);
+        if remaining > 0 {
+            return Err((
+                self,
+                error::TransferError::RemainingResources(remaining).into(),
+            ));
+        }
+
+        match transfer::ResourceAck::recv(self.state.secure_stream()).await {
+            Ok(_) => {
+                self.state.secure_stream().set_compression(None);
+                Ok(Client {
+                    state: self.state.completed(),
+                    conf: self.conf,
+                })
+            }
+            Err(error) => Err((self, error)),
+        }
+    }
+}
+
+impl Client<ReceiveResourceRequested> {
+    pub fn resources(&self) -> &[transfer::Resource] {
+        &self.state.context().resources
+    }
+
+    pub fn expiry(&self) -> chrono::DateTime<chrono::Utc> {
+        self.state.context().expiry
+    }
+
+    // Receive the next file in the declared order, skipping the directories. The digest of
+    // the file is verified once it has been written.
+    pub async fn receive_resource(&mut self, writer: impl Write + Unpin) -> Result<(), Error> {
+        self.resume_resource(io::empty(), writer).await
+    }
+
+    // Like `receive_resource`, but for a file resumed from a non-zero offset. Only the bytes
+    // from the offset on are written, such that the partial file can be appended to, while
+    // the partial file is read to verify the digest of the whole file.
+    pub async fn resume_resource(
+        &mut self,
+        partial: impl Read + Unpin,
+        writer: impl Write + Unpin,
+    ) -> Result<(), Error> {
+        let (secure_stream, context) = self.state.parts_mut();
+        context.received = next_file(&context.resources, context.received);
+        let resource = context
+            .resources
+            .get(context.received)
+            .ok_or(error::TransferError::NoRemainingResource)?;
+        let size = resource.size;
+        let index = context.received;
+        let offset = context.offsets[index];
+        let mut progress = context.progress.progress(index, resource, offset);
+        context.received += 1;
+
+        let mut hasher = crypto::ResourceHasher::new();
+        transfer::hash_partial(&mut hasher, partial, offset).await?;
+        let tracker = &context.progress;
+        let observer = &mut self.conf.progress_observer;
+        let digest = transfer::recv_resource(secure_stream, size - offset, writer, hasher, |len| {
+            tracker.advance(&mut progress, len);
+            if let Some(observer) = observer {
+                observer(&progress);
+            }
+        })
+        .await?;
+        // NOTE: The digest sent at the end is also checked against the one declared by the
+        // sender, such that a server cannot replace the file.
+        if context.digests.get(index) != Some(&Some(digest)) {
+            return Err(error::TransferError::DigestMismatch.into());
+        }
+        context.progress.complete(size);
+
+        Ok(())
     }
 
-    async fn receive_resource_request(mut self) -> Result<Client<ReceiveResourceRequested>, Error> {
-        todo!()
+    pub async fn receive_completed(mut self) -> Result<Client<UpgradedConnection>, (Self, Error)> {
+        let context = self.state.context();
+        let remaining = remaining_files(&context.resources, context.received);
+        if remaining > 0 {
+            return Err((
+                self,
+                error::TransferError::RemainingResources(remaining).into(),
+            ));
+        }
+
+        match transfer::ResourceAck.send(self.state.secure_stream()).await {
+            Ok(_) => {
+                self.state.secure_stream().set_compression(None);
+                Ok(Client {
+                    state: self.state.completed(),
+                    conf: self.conf,
+                })
+            }
+            Err(error) => Err((self, error)),
+        }
     }
 }
 
@@ -145,8 +594,62 @@ impl<S: PlainState, T: SecureState<DowngradeState = S>> Client<T> {
     }
 }
 
+// The index of the next file from the given index on, as directories carry no data.
+fn next_file(resources: &[transfer::Resource], index: usize) -> usize {
+    index
+        + resources
+            .iter()
+            .skip(index)
+            .take_while(|resource| resource.is_dir())
+            .count()
+}
+
+fn remaining_files(resources: &[transfer::Resource], index: usize) -> usize {
+    resources
+        .iter()
+        .skip(index)
+        .filter(|resource| !resource.is_dir())
+        .count()
+}
+
+// The server may only pick one of the compressions offered in the request.
+fn check_compression(
+    offered: &[transfer::Compression],
+    picked: Option<transfer::Compression>,
+) -> Result<(), error::ResourceRequestError> {
+    match picked {
+        Some(compression) if !offered.contains(&compression) => Err(
+            error::ResourceRequestError::UnexpectedCompression(compression),
+        ),
+        _ => Ok(()),
+    }
+}
+
+impl<T: State> Client<T> {
+    // Register an observer of the progress of sending or receiving resources, replacing any
+    // previous one.
+    pub fn set_progress_observer(&mut self, observer: impl FnMut(&Progress) + Send + 'static) {
+        self.conf.progress_observer = Some(Box::new(observer));
+    }
+
+    // Restrict the cipher suites offered in the handshake, in order of preference.
+    pub fn set_cipher_suites(&mut self, suites: &[CipherSuite]) {
+        self.conf.cipher_suites = suites.to_vec();
+    }
+
+    // Set the amount of data sealed under a key after which it is updated, taking effect
+    // from the handshake on.
+    pub fn set_key_update_limits(&mut self, limits: KeyUpdateLimits) {
+        self.conf.key_update_limits = limits;
+    }
+}
+
 impl<T: PlainState> Client<T> {
-    async fn disconnect(mut self) -> Result<Client<NoConnection>, Error> {
+    pub(crate) async fn recv(&mut self) -> Result<Message, Error> {
+        self.state.plain_stream().recv().await
+    }
+
+    pub async fn disconnect(mut self) -> Result<Client<NoConnection>, Error> {
         self.state
             .plain_stream()
             .send(handshake::DisconnectMessage {}.into())
@@ -182,21 +685,23 @@ impl<T: PlainState> Client<T> {
     async fn request_len_limit_responded(
         &mut self,
         response: len_limit::AdjustLenLimitResponse,
-    ) -> Result<(), Error> {
+    ) -> Result<bool, Error> {
         let len_limit = self
             .conf
             .requested_len_limit
             .take()
             .ok_or(error::LenLimitAdjustmentError::NoOngoingRequest)?;
 
-        if response.has_accepted() {
+        let has_accepted = response.has_accepted();
+        if has_accepted {
             self.state.plain_stream().set_len_limit(len_limit);
         }
 
-        Ok(())
+        Ok(has_accepted)
     }
 
-    async fn respond_len_limit(&mut self, 
+    async fn respond_len_limit(
+        &mut self,
         request: len_limit::AdjustLenLimitRequest,
         decision_callback: impl FnOnce(usize) -> bool,
     ) -> Result<(), Error> {
@@ -205,7 +710,7 @@ impl<T: PlainState> Client<T> {
         } else {
             decision_callback(request.len_limit())
         };
-        
+
         self.state
             .plain_stream()
             .send(len_limit::AdjustLenLimitResponse::new(decision).into())
@@ -217,9 +722,13 @@ impl<T: PlainState> Client<T> {
 
 #[cfg(test)]
 mod test {
-    use async_std::{net::TcpListener, task};
+    use async_std::net::{SocketAddr, TcpListener, TcpStream};
+    use async_std::task;
+    use ring::agreement;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
 
     use super::*;
+    use crate::proto::stream::SecureStream;
 
     // NOTE: run after command `netcat -l 8080` is executed to listen on TCP port 8080
     #[ignore]
@@ -238,4 +747,362 @@ mod test {
             .unwrap();
         let client = Client::new().connect(BaseStream::Tcp(tcp_stream));
     }
+
+    async fn connect_upgraded(
+        addr: SocketAddr,
+        sig_pub_key: Vec<u8>,
+    ) -> Client<UpgradedConnection> {
+        let tcp_stream = TcpStream::connect(addr).await.unwrap();
+        let client = Client::new().connect(BaseStream::Tcp(tcp_stream));
+        let mut client = client.client_hello().await.ok().unwrap();
+        let server_hello_msg =
+            handshake::ServerHelloMessage::try_from(client.recv().await.unwrap()).unwrap();
+        client
+            .server_hello(server_hello_msg, ServerSigPubKey::new(sig_pub_key))
+            .await
+            .ok()
+            .unwrap()
+    }
+
+    async fn accept_upgraded(
+        listener: &TcpListener,
+        sig_key_pair: &Ed25519KeyPair,
+    ) -> SecureStream {
+        let (tcp_stream, _) = listener.accept().await.unwrap();
+        let mut stream = PlainStream::from(BaseStream::Tcp(tcp_stream));
+
+        let client_hello_msg =
+            handshake::ClientHelloMessage::try_from(stream.recv().await.unwrap()).unwrap();
+        let (private_key, public_key) = crypto::generate_ephemeral_key_pair().unwrap();
+        let (server_hello_msg, nonces) = crypto::sign_server_hello(
+            &client_hello_msg,
+            crypto::generate_nonce().await.unwrap(),
+            public_key.as_ref().try_into().unwrap(),
+            proto::CURRENT_PROTOCOL_VERSION,
+            CipherSuite::Aes128Gcm,
+            sig_key_pair,
+        );
+        stream.send(server_hello_msg.into()).await.unwrap();
+        stream.set_version(proto::CURRENT_PROTOCOL_VERSION);
+
+        let session_secrets = crypto::generate_session_secrets(
+            private_key,
+            agreement::UnparsedPublicKey::new(
+                &agreement::X25519,
+                client_hello_msg.public_key_bytes,
+            ),
+            nonces,
+            CipherSuite::Aes128Gcm,
+            crypto::handshake_transcript(&client_hello_msg, &server_hello_msg),
+            Side::Server,
+        )
+        .await
+        .unwrap();
+        SecureStream::new(stream, session_secrets)
+    }
+
+    fn send_resource_request() -> transfer::SendResourceRequest {
+        transfer::SendResourceRequest {
+            resources: vec![transfer::Resource::file(1024, "test.txt")],
+            expiry_duration: Some(chrono::Duration::hours(1)),
+            receiver_control: None,
+            compression: Vec::new(),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_send_resource_request() {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = sig_key_pair.public_key().as_ref().to_owned();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let expiry = chrono::Utc::now() + chrono::Duration::hours(1);
+
+        let join_handle = task::spawn(async move {
+            let mut client = connect_upgraded(addr, sig_pub_key).await;
+
+            for expected in [
+                error::ResourceRequestError::InvalidReceiverControl,
+                error::ResourceRequestError::InvalidExpiry,
+                error::ResourceRequestError::ResourceTooLarge,
+                error::ResourceRequestError::UnexpectedCompression(transfer::Compression::Lz4),
+            ] {
+                let (returned_client, error) = client
+                    .send_resource_request(send_resource_request())
+                    .await
+                    .err()
+                    .unwrap();
+                assert!(matches!(error, Error::ResourceRequest(inner) if inner == expected));
+                client = returned_client;
+            }
+
+            let client = client
+                .send_resource_request(send_resource_request())
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(
+                client.resource_id(),
+                &transfer::ResourceId(vec![1, 2, 3, 4])
+            );
+            assert_eq!(client.expiry(), expiry);
+        });
+
+        let mut stream = accept_upgraded(&listener, &sig_key_pair).await;
+        for response in [
+            transfer::SendResourceResponse::InvalidReceiverControl,
+            transfer::SendResourceResponse::InvalidExpiry,
+            transfer::SendResourceResponse::ResourceTooLarge,
+            // The server picks a compression which has not been offered.
+            transfer::SendResourceResponse::Ok {
+                id: transfer::ResourceId(vec![1, 2, 3, 4]),
+                expiry,
+                compression: Some(transfer::Compression::Lz4),
+            },
+            transfer::SendResourceResponse::Ok {
+                id: transfer::ResourceId(vec![1, 2, 3, 4]),
+                expiry,
+                compression: None,
+            },
+        ] {
+            let request = transfer::SendResourceRequest::recv(&mut stream)
+                .await
+                .unwrap();
+            assert_eq!(request, send_resource_request());
+            response.send(&mut stream).await.unwrap();
+        }
+
+        join_handle.await;
+    }
+
+    #[async_std::test]
+    async fn test_receive_resource_request() {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = sig_key_pair.public_key().as_ref().to_owned();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let expiry = chrono::Utc::now() + chrono::Duration::hours(1);
+
+        let join_handle = task::spawn(async move {
+            let client = connect_upgraded(addr, sig_pub_key).await;
+            let request = || transfer::ReceiveResourceRequest {
+                id: transfer::ResourceId(vec![1, 2, 3, 4]),
+                control: None,
+                offsets: Vec::new(),
+                compression: Vec::new(),
+            };
+
+            let (client, error) = client
+                .receive_resource_request(request(), None)
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(
+                error,
+                Error::ResourceRequest(error::ResourceRequestError::Failed)
+            ));
+
+            let client = client
+                .receive_resource_request(request(), None)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(
+                client.resources(),
+                &[
+                    transfer::Resource::file(1024, "a.txt"),
+                    transfer::Resource::file(2048, "b.txt")
+                ]
+            );
+            assert_eq!(client.expiry(), expiry);
+        });
+
+        let mut stream = accept_upgraded(&listener, &sig_key_pair).await;
+        for response in [
+            transfer::ReceiveResourceResponse::Failed,
+            transfer::ReceiveResourceResponse::Ok {
+                resources: vec![
+                    transfer::Resource::file(1024, "a.txt"),
+                    transfer::Resource::file(2048, "b.txt"),
+                ],
+                digests: vec![Some([0; crypto::RESOURCE_DIGEST_LEN]); 2],
+                expiry,
+                compression: None,
+            },
+        ] {
+            transfer::ReceiveResourceRequest::recv(&mut stream)
+                .await
+                .unwrap();
+            response.send(&mut stream).await.unwrap();
+        }
+
+        join_handle.await;
+    }
+
+    #[async_std::test]
+    async fn test_send_and_receive_resource() {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = sig_key_pair.public_key().as_ref().to_owned();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let expiry = chrono::Utc::now() + chrono::Duration::hours(1);
+        let data = (0..transfer::RESOURCE_CHUNK_LEN * 2 + 7)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let size = data.len() as u64;
+
+        let client_data = data.clone();
+        let join_handle = task::spawn(async move {
+            let mut client = connect_upgraded(addr, sig_pub_key).await;
+            let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let observer_events = events.clone();
+            client.set_progress_observer(move |progress| {
+                observer_events.lock().unwrap().push(progress.clone())
+            });
+            let client = client
+                .send_resource_request(transfer::SendResourceRequest {
+                    resources: vec![transfer::Resource::file(size, "test.bin")],
+                    expiry_duration: None,
+                    receiver_control: None,
+                    compression: transfer::Compression::SUPPORTED.to_vec(),
+                })
+                .await
+                .ok()
+                .unwrap();
+
+            let (client, error) = client.send_completed().await.err().unwrap();
+            assert!(matches!(
+                error,
+                Error::Transfer(error::TransferError::RemainingResources(1))
+            ));
+            let mut client = client;
+            client.send_resource(client_data.as_slice()).await.unwrap();
+            assert!(matches!(
+                client.send_resource(client_data.as_slice()).await,
+                Err(Error::Transfer(error::TransferError::NoRemainingResource))
+            ));
+            let client = client.send_completed().await.ok().unwrap();
+
+            // One event before the resource, then one per chunk.
+            let sent_events = std::mem::take(&mut *events.lock().unwrap());
+            assert_eq!(sent_events.len(), 4);
+            assert_eq!(sent_events[0].transferred, 0);
+            let last = sent_events.last().unwrap();
+            assert_eq!(
+                (last.index, last.name.as_str(), last.transferred, last.size),
+                (0, "test.bin", size, size)
+            );
+            assert_eq!((last.total_transferred, last.total_size), (size, size));
+
+            let mut client = client
+                .receive_resource_request(
+                    transfer::ReceiveResourceRequest {
+                        id: transfer::ResourceId(vec![1, 2, 3, 4]),
+                        control: None,
+                        offsets: Vec::new(),
+                        compression: Vec::new(),
+                    },
+                    None,
+                )
+                .await
+                .ok()
+                .unwrap();
+            let mut received = Vec::new();
+            client.receive_resource(&mut received).await.unwrap();
+            assert_eq!(received, client_data);
+            let client = client.receive_completed().await.ok().unwrap();
+            let received_events = std::mem::take(&mut *events.lock().unwrap());
+            assert_eq!(received_events.len(), 4);
+            assert_eq!(received_events.last().unwrap().total_transferred, size);
+
+            // The second time, the file is resumed, but the digest declared by the
+            // server does not match the data.
+            let mut client = client
+                .receive_resource_request(
+                    transfer::ReceiveResourceRequest {
+                        id: transfer::ResourceId(vec![1, 2, 3, 4]),
+                        control: None,
+                        offsets: vec![5],
+                        compression: transfer::Compression::SUPPORTED.to_vec(),
+                    },
+                    None,
+                )
+                .await
+                .ok()
+                .unwrap();
+            let mut received = client_data[..5].to_vec();
+            assert!(matches!(
+                client
+                    .resume_resource(&client_data[..5], &mut received)
+                    .await,
+                Err(Error::Transfer(error::TransferError::DigestMismatch))
+            ));
+            assert_eq!(received, client_data);
+        });
+
+        let mut stream = accept_upgraded(&listener, &sig_key_pair).await;
+
+        transfer::SendResourceRequest::recv(&mut stream)
+            .await
+            .unwrap();
+        // The upload is compressed, while the first download is not.
+        transfer::SendResourceResponse::Ok {
+            id: transfer::ResourceId(vec![1, 2, 3, 4]),
+            expiry,
+            compression: Some(transfer::Compression::Lz4),
+        }
+        .send(&mut stream)
+        .await
+        .unwrap();
+        stream.set_compression(Some(transfer::Compression::Lz4));
+        let mut uploaded = Vec::new();
+        let digest = transfer::recv_resource(
+            &mut stream,
+            size,
+            &mut uploaded,
+            crypto::ResourceHasher::new(),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(uploaded, data);
+        transfer::ResourceAck.send(&mut stream).await.unwrap();
+        stream.set_compression(None);
+
+        transfer::ReceiveResourceRequest::recv(&mut stream)
+            .await
+            .unwrap();
+        transfer::ReceiveResourceResponse::Ok {
+            resources: vec![transfer::Resource::file(size, "test.bin")],
+            digests: vec![Some(digest)],
+            expiry,
+            compression: None,
+        }
+        .send(&mut stream)
+        .await
+        .unwrap();
+        transfer::send_resource(&mut stream, size, uploaded.as_slice(), None, |_| {})
+            .await
+            .unwrap();
+        transfer::ResourceAck::recv(&mut stream).await.unwrap();
+
+        transfer::ReceiveResourceRequest::recv(&mut stream)
+            .await
+            .unwrap();
+        transfer::ReceiveResourceResponse::Ok {
+            resources: vec![transfer::Resource::file(size, "test.bin")],
+            digests: vec![Some([0; crypto::RESOURCE_DIGEST_LEN])],
+            expiry,
+            compression: Some(transfer::Compression::Lz4),
+        }
+        .send(&mut stream)
+        .await
+        .unwrap();
+        stream.set_compression(Some(transfer::Compression::Lz4));
+        transfer::send_resource(&mut stream, size - 5, &uploaded[5..], Some(digest), |_| {})
+            .await
+            .unwrap();
+
+        join_handle.await;
+    }
 }
@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use crate::proto::message::transfer::Resource;
+
+// The observer is called whenever a chunk of a file has been sent or received, and once
+// before each file.
+pub(super) type ProgressObserver = Box<dyn FnMut(&Progress) + Send>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    // The index in the manifest and path of the current file.
+    pub index: usize,
+    pub name: String,
+    // The bytes transferred of the current file, and its declared size.
+    pub transferred: u64,
+    pub size: u64,
+    // The bytes transferred of all files, and their total declared size.
+    pub total_transferred: u64,
+    pub total_size: u64,
+    // The average throughput in bytes per second since the resources were requested,
+    // excluding the bytes of a resumed download that were already received before.
+    pub throughput: f64,
+}
+
+// Tracks the progress of all resources of a request.
+pub(super) struct ProgressTracker {
+    started: Instant,
+    total_size: u64,
+    // The bytes transferred of the completed files.
+    completed: u64,
+    // The bytes skipped by resuming from an offset so far.
+    resumed: u64,
+}
+
+impl ProgressTracker {
+    pub(super) fn new(resources: &[Resource]) -> Self {
+        Self {
+            started: Instant::now(),
+            total_size: resources.iter().map(|resource| resource.size).sum(),
+            completed: 0,
+            resumed: 0,
+        }
+    }
+
+    // The progress of a file about to be transferred from the given offset.
+    pub(super) fn progress(&mut self, index: usize, resource: &Resource, offset: u64) -> Progress {
+        self.resumed += offset;
+        Progress {
+            index,
+            name: resource.path.clone(),
+            transferred: offset,
+            size: resource.size,
+            total_transferred: self.completed + offset,
+            total_size: self.total_size,
+            throughput: self.throughput(self.completed + offset),
+        }
+    }
+
+    pub(super) fn advance(&self, progress: &mut Progress, len: u64) {
+        progress.transferred += len;
+        progress.total_transferred += len;
+        progress.throughput = self.throughput(progress.total_transferred);
+    }
+
+    pub(super) fn complete(&mut self, size: u64) {
+        self.completed += size;
+    }
+
+    fn throughput(&self, transferred: u64) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (transferred - self.resumed) as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
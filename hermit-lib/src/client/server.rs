@@ -11,6 +11,12 @@ impl ServerSigPubKey {
     }
 }
 
+impl From<[u8; signature::ED25519_PUBLIC_KEY_LEN]> for ServerSigPubKey {
+    fn from(sig_pub_key: [u8; signature::ED25519_PUBLIC_KEY_LEN]) -> Self {
+        Self::new(sig_pub_key.to_vec())
+    }
+}
+
 impl AsRef<signature::UnparsedPublicKey<Box<[u8]>>> for ServerSigPubKey {
     fn as_ref(&self) -> &signature::UnparsedPublicKey<Box<[u8]>> {
         &self.0
@@ -1,25 +1,16 @@
+use super::progress::ProgressTracker;
 use crate::crypto::secrets::SessionSecrets;
-use crate::crypto::NONCE_LEN;
-use crate::proto::stream::{Plain, PlainStream, Secure, SecureStream};
+use crate::crypto::RESOURCE_DIGEST_LEN;
+use crate::proto::message::handshake::ClientHelloMessage;
+use crate::proto::message::transfer::{Resource, ResourceId};
+use crate::proto::stream::{PlainStream, Secure, SecureStream};
+use crate::state::{PlainState, SecureState, State};
 use crate::{nil, plain, secure};
 
-
-pub trait State {}
-pub trait PlainState: State {
-    type PlainStream: Plain;
-    fn plain_stream(&mut self) -> &mut Self::PlainStream;
-}
-pub trait SecureState: PlainState {
-    type DowngradeState: PlainState;
-    type SecureStream: Secure;
-    fn secure_stream(&mut self) -> &mut Self::SecureStream;
-    fn downgrade(self) -> Self::DowngradeState;
-}
-
-pub(super) struct NoConnection;
+pub struct NoConnection;
 nil!(NoConnection);
 
-pub(super) struct InsecureConnection(PlainStream);
+pub struct InsecureConnection(PlainStream);
 plain!(InsecureConnection);
 impl InsecureConnection {
     pub(super) fn new(stream: PlainStream) -> Self {
@@ -28,25 +19,17 @@ impl InsecureConnection {
 }
 
 pub(super) struct HandshakeContext {
-    pub(super) nonce: [u8; NONCE_LEN],
+    pub(super) client_hello_msg: ClientHelloMessage,
     pub(super) private_key: ring::agreement::EphemeralPrivateKey,
 }
 
-pub(super) struct HandshakingConnection(
-    PlainStream,
-    Option<HandshakeContext>,
-);
+pub struct HandshakingConnection(PlainStream, Option<HandshakeContext>);
 plain!(HandshakingConnection);
 impl HandshakingConnection {
-    pub(super) fn new(
-        state: InsecureConnection,
-        handshake_parameters: HandshakeContext,
-    ) -> Self {
+    pub(super) fn new(state: InsecureConnection, handshake_parameters: HandshakeContext) -> Self {
         Self(state.0, Some(handshake_parameters))
     }
-    pub(super) fn context(
-        &mut self,
-    ) -> Option<HandshakeContext> {
+    pub(super) fn context(&mut self) -> Option<HandshakeContext> {
         self.1.take()
     }
 
@@ -55,7 +38,7 @@ impl HandshakingConnection {
     }
 }
 
-pub(super) struct UpgradedConnection(SecureStream);
+pub struct UpgradedConnection(SecureStream);
 secure!(UpgradedConnection);
 impl UpgradedConnection {
     pub(super) fn new(state: HandshakingConnection, session_secrets: SessionSecrets) -> Self {
@@ -63,8 +46,63 @@ impl UpgradedConnection {
     }
 }
 
-pub(super) struct SendResourceRequested(SecureStream);
+pub(super) struct SendResourceContext {
+    pub(super) resources: Vec<Resource>,
+    pub(super) id: ResourceId,
+    pub(super) expiry: chrono::DateTime<chrono::Utc>,
+    // The number of resources sent so far, including the skipped directories.
+    pub(super) sent: usize,
+    pub(super) progress: ProgressTracker,
+}
+
+pub struct SendResourceRequested(SecureStream, SendResourceContext);
 secure!(SendResourceRequested);
+impl SendResourceRequested {
+    pub(super) fn new(state: UpgradedConnection, context: SendResourceContext) -> Self {
+        Self(state.0, context)
+    }
+
+    pub(super) fn context(&self) -> &SendResourceContext {
+        &self.1
+    }
+
+    pub(super) fn parts_mut(&mut self) -> (&mut SecureStream, &mut SendResourceContext) {
+        (&mut self.0, &mut self.1)
+    }
+
+    pub(super) fn completed(self) -> UpgradedConnection {
+        UpgradedConnection(self.0)
+    }
+}
+
+pub(super) struct ReceiveResourceContext {
+    pub(super) resources: Vec<Resource>,
+    pub(super) expiry: chrono::DateTime<chrono::Utc>,
+    // The number of resources received so far, including the skipped directories.
+    pub(super) received: usize,
+    // The offset from which each resource is received.
+    pub(super) offsets: Vec<u64>,
+    // The digest of each file declared by the sender.
+    pub(super) digests: Vec<Option<[u8; RESOURCE_DIGEST_LEN]>>,
+    pub(super) progress: ProgressTracker,
+}
 
-pub(super) struct ReceiveResourceRequested(SecureStream);
+pub struct ReceiveResourceRequested(SecureStream, ReceiveResourceContext);
 secure!(ReceiveResourceRequested);
+impl ReceiveResourceRequested {
+    pub(super) fn new(state: UpgradedConnection, context: ReceiveResourceContext) -> Self {
+        Self(state.0, context)
+    }
+
+    pub(super) fn context(&self) -> &ReceiveResourceContext {
+        &self.1
+    }
+
+    pub(super) fn parts_mut(&mut self) -> (&mut SecureStream, &mut ReceiveResourceContext) {
+        (&mut self.0, &mut self.1)
+    }
+
+    pub(super) fn completed(self) -> UpgradedConnection {
+        UpgradedConnection(self.0)
+    }
+}
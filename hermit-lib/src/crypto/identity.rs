@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use async_std::fs;
+use async_std::prelude::*;
+use ring::signature::{self, KeyPair};
+
+use crate::error;
+
+// NOTE: The identity key is stored as an unencrypted PKCS#8 v2 document, readable only by
+// the owner. Receiver keys for `ReceiverControl::PublicKey` are stored in the same way.
+const IDENTITY_KEY_FILE_MODE: u32 = 0o600;
+
+// Generate a new identity key and write it to `path`, which must not exist yet.
+pub async fn generate_identity_key<P: AsRef<Path>>(
+    path: P,
+) -> Result<signature::Ed25519KeyPair, error::Error> {
+    let document = super::generate_signature_key_document()?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    async_std::os::unix::fs::OpenOptionsExt::mode(&mut options, IDENTITY_KEY_FILE_MODE);
+
+    let mut file = options.open(path.as_ref()).await?;
+    file.write_all(document.as_ref()).await?;
+    file.sync_all().await?;
+
+    Ok(signature::Ed25519KeyPair::from_pkcs8(document.as_ref())
+        .map_err(error::CryptoError::from)?)
+}
+
+pub async fn load_identity_key<P: AsRef<Path>>(
+    path: P,
+) -> Result<signature::Ed25519KeyPair, error::Error> {
+    let document = fs::read(path.as_ref()).await?;
+    Ok(signature::Ed25519KeyPair::from_pkcs8(&document).map_err(error::CryptoError::from)?)
+}
+
+// Export the public key, which clients use to verify the server.
+pub fn export_public_key(
+    key_pair: &signature::Ed25519KeyPair,
+) -> [u8; signature::ED25519_PUBLIC_KEY_LEN] {
+    // SAFETY: Ed25519 public key has the correct length
+    key_pair.public_key().as_ref().try_into().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::ServerSigPubKey;
+
+    #[async_std::test]
+    async fn test_generate_load_identity_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identity.key");
+
+        let key_pair = generate_identity_key(&path).await.unwrap();
+        let loaded_key_pair = load_identity_key(&path).await.unwrap();
+        assert_eq!(
+            export_public_key(&key_pair),
+            export_public_key(&loaded_key_pair)
+        );
+
+        // A client verifies signatures of the loaded key with the exported public key.
+        let sig_pub_key = ServerSigPubKey::from(export_public_key(&key_pair));
+        sig_pub_key
+            .as_ref()
+            .verify(b"message", loaded_key_pair.sign(b"message").as_ref())
+            .unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, IDENTITY_KEY_FILE_MODE);
+        }
+
+        // An existing identity key is never overwritten.
+        assert!(matches!(
+            generate_identity_key(&path).await,
+            Err(error::Error::IONetwork(_))
+        ));
+
+        std::fs::write(&path, b"not a key").unwrap();
+        assert!(matches!(
+            load_identity_key(&path).await,
+            Err(error::Error::Crypto(error::CryptoError::KeyRejected(_)))
+        ));
+    }
+}
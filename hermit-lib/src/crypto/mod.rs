@@ -1,18 +1,34 @@
+pub mod identity;
+pub(crate) mod password;
 pub mod secrets;
+pub(crate) mod suite;
 
 use std::sync::OnceLock;
 
 use async_std::task;
-use ring::{aead, agreement, digest, hkdf, rand, signature};
+use ring::{aead, agreement, digest, hkdf, pkcs8, rand, signature};
 
 use crate::proto::message::handshake;
 use crate::{error, proto};
 
+pub use self::suite::CipherSuite;
+
 pub(crate) const NONCE_LEN: usize = 16;
 pub(crate) const ED25519_SIGNATURE_LEN: usize = 64;
+pub(crate) const ED25519_PUBLIC_KEY_LEN: usize = signature::ED25519_PUBLIC_KEY_LEN;
 pub(crate) const X25519_PUBLIC_KEY_LEN: usize = 32;
-pub(crate) const SIGNED_CONTENT_LEN: usize = 2 * NONCE_LEN + X25519_PUBLIC_KEY_LEN;
-pub(crate) const AEAD_KEY_LEN: usize = 16;
+pub(crate) const SIGNED_CONTENT_LEN: usize = 2 * NONCE_LEN
+    + X25519_PUBLIC_KEY_LEN
+    + proto::PROTOCOL_VERSIONS_LEN
+    + suite::CIPHER_SUITES_LEN
+    + 2;
+// The longest key of the cipher suites.
+pub(crate) const MAX_AEAD_KEY_LEN: usize = 32;
+pub(crate) const RECEIVER_CHALLENGE_LEN: usize = 32;
+pub(crate) const RESOURCE_DIGEST_LEN: usize = digest::SHA256_OUTPUT_LEN;
+pub(crate) const TRANSCRIPT_LEN: usize = digest::SHA256_OUTPUT_LEN;
+const RECEIVER_CHALLENGE_LABEL: &[u8] = b"hermit receiver challenge";
+const CLIENT_AUTH_LABEL: &[u8] = b"hermit client authentication";
 
 static SYSTEM_RANDOM: OnceLock<rand::SystemRandom> = OnceLock::new();
 
@@ -28,6 +44,12 @@ pub(crate) async fn generate_nonce() -> Result<[u8; NONCE_LEN], error::CryptoErr
     .await
 }
 
+pub(crate) fn fill_random(buffer: &mut [u8]) -> Result<(), error::CryptoError> {
+    let rng = SYSTEM_RANDOM.get_or_init(rand::SystemRandom::new);
+    rand::SecureRandom::fill(rng, buffer)?;
+    Ok(())
+}
+
 pub(crate) fn generate_ephemeral_key_pair(
 ) -> Result<(agreement::EphemeralPrivateKey, agreement::PublicKey), error::CryptoError> {
     let rng = SYSTEM_RANDOM.get_or_init(rand::SystemRandom::new);
@@ -38,31 +60,119 @@ pub(crate) fn generate_ephemeral_key_pair(
 
 pub(crate) fn generate_signature_key_pair() -> Result<signature::Ed25519KeyPair, error::CryptoError>
 {
-    let rng = SYSTEM_RANDOM.get_or_init(rand::SystemRandom::new);
-    let sig_document = signature::Ed25519KeyPair::generate_pkcs8(rng)?;
+    let sig_document = generate_signature_key_document()?;
     Ok(signature::Ed25519KeyPair::from_pkcs8(
         sig_document.as_ref(),
     )?)
 }
 
+// Generate a signature key pair as a PKCS#8 v2 document, such that it can be stored.
+pub(crate) fn generate_signature_key_document() -> Result<pkcs8::Document, error::CryptoError> {
+    let rng = SYSTEM_RANDOM.get_or_init(rand::SystemRandom::new);
+    Ok(signature::Ed25519KeyPair::generate_pkcs8(rng)?)
+}
+
+// LAYOUT: client_nonce || server_nonce || server_public_key || client_versions
+//         || client_suites || version || suite
+// NOTE: The versions and cipher suites offered are signed along with those picked, such that
+// an attacker cannot downgrade them by stripping offers from the client hello.
+fn server_hello_signed_content(
+    client_hello_msg: &handshake::ClientHelloMessage,
+    server_nonce: &[u8; NONCE_LEN],
+    server_public_key_bytes: &[u8; X25519_PUBLIC_KEY_LEN],
+    version: &[u8; 1],
+    suite: &[u8; 1],
+) -> [u8; SIGNED_CONTENT_LEN] {
+    let mut message = [0u8; SIGNED_CONTENT_LEN];
+    let mut offset = 0;
+    for part in [
+        client_hello_msg.nonce.as_slice(),
+        server_nonce,
+        server_public_key_bytes,
+        &client_hello_msg.versions,
+        &client_hello_msg.suites,
+        version,
+        suite,
+    ] {
+        message[offset..offset + part.len()].copy_from_slice(part);
+        offset += part.len();
+    }
+    message
+}
+
+pub(crate) fn sign_server_hello(
+    client_hello_msg: &handshake::ClientHelloMessage,
+    server_nonce: [u8; NONCE_LEN],
+    server_public_key_bytes: [u8; X25519_PUBLIC_KEY_LEN],
+    version: proto::ProtocolVersion,
+    suite: CipherSuite,
+    server_sig_key_pair: &signature::Ed25519KeyPair,
+) -> (handshake::ServerHelloMessage, [u8; 2 * NONCE_LEN]) {
+    let version = [version.into()];
+    let suite = [suite.into()];
+    let message = server_hello_signed_content(
+        client_hello_msg,
+        &server_nonce,
+        &server_public_key_bytes,
+        &version,
+        &suite,
+    );
+
+    (
+        handshake::ServerHelloMessage {
+            nonce: server_nonce,
+            public_key_bytes: server_public_key_bytes,
+            // SAFETY: Ed25519 public key has the correct length
+            sig_public_key_bytes: signature::KeyPair::public_key(server_sig_key_pair)
+                .as_ref()
+                .try_into()
+                .unwrap(),
+            // SAFETY: Ed25519 signature has the correct length
+            signature: server_sig_key_pair
+                .sign(&message)
+                .as_ref()
+                .try_into()
+                .unwrap(),
+            version,
+            suite,
+        },
+        <[u8; 2 * NONCE_LEN]>::try_from(&message[..2 * NONCE_LEN]).unwrap(),
+    )
+}
+
 pub(crate) fn verify_server_hello(
     handshake::ServerHelloMessage {
         nonce: server_nonce,
         public_key_bytes: server_public_key_bytes,
+        sig_public_key_bytes,
         signature,
+        version,
+        suite,
     }: handshake::ServerHelloMessage,
-    client_nonce: [u8; NONCE_LEN],
+    client_hello_msg: &handshake::ClientHelloMessage,
     server_sig_pub_key: &signature::UnparsedPublicKey<impl AsRef<[u8]>>,
 ) -> Result<(agreement::UnparsedPublicKey<[u8; 32]>, [u8; 2 * NONCE_LEN]), error::CryptoError> {
-    // LAYOUT: client_nonce || server_nonce || server_public_key
-    let mut message = [0u8; SIGNED_CONTENT_LEN];
-    message[..NONCE_LEN].copy_from_slice(&client_nonce);
-    message[NONCE_LEN..2 * NONCE_LEN].copy_from_slice(&server_nonce);
-    message[2 * NONCE_LEN..].copy_from_slice(&server_public_key_bytes);
+    let message = server_hello_signed_content(
+        client_hello_msg,
+        &server_nonce,
+        &server_public_key_bytes,
+        &version,
+        &suite,
+    );
 
     server_sig_pub_key
         .verify(&message, &signature)
-        .map_err(|_| error::CryptoError::BadServerHelloSignature)?;
+        .map_err(|_| {
+            // NOTE: If the signature is valid under the key presented by the server instead,
+            // the server's identity has changed (or is being impersonated), as opposed to the
+            // message being corrupted.
+            let presented_sig_pub_key =
+                signature::UnparsedPublicKey::new(&signature::ED25519, sig_public_key_bytes);
+            match presented_sig_pub_key.verify(&message, &signature) {
+                Ok(()) => error::CryptoError::ServerSigPubKeyMismatch,
+                Err(_) => error::CryptoError::BadServerHelloSignature,
+            }
+        })?;
 
     Ok((
         agreement::UnparsedPublicKey::new(&agreement::X25519, server_public_key_bytes),
@@ -70,6 +180,118 @@ pub(crate) fn verify_server_hello(
     ))
 }
 
+// The digest of the hello messages exchanged in the handshake, which covers the nonces and
+// ephemeral public keys of both sides, as well as the version and cipher suite picked.
+pub(crate) fn handshake_transcript(
+    client_hello_msg: &handshake::ClientHelloMessage,
+    server_hello_msg: &handshake::ServerHelloMessage,
+) -> [u8; TRANSCRIPT_LEN] {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(proto::message::Message::from(*client_hello_msg).as_ref());
+    context.update(proto::message::Message::from(*server_hello_msg).as_ref());
+    // SAFETY: output has the correct length
+    context.finish().as_ref().try_into().unwrap()
+}
+
+// LAYOUT: label || transcript
+// NOTE: The transcript differs in every session, such that a signature cannot be replayed
+// in another one.
+fn client_auth_signed_content(session_secrets: &secrets::SessionSecrets) -> Vec<u8> {
+    [CLIENT_AUTH_LABEL, session_secrets.transcript()].concat()
+}
+
+pub(crate) fn sign_client_auth(
+    session_secrets: &secrets::SessionSecrets,
+    client_key_pair: &signature::Ed25519KeyPair,
+) -> [u8; ED25519_SIGNATURE_LEN] {
+    let message = client_auth_signed_content(session_secrets);
+    // SAFETY: Ed25519 signature has the correct length
+    client_key_pair.sign(&message).as_ref().try_into().unwrap()
+}
+
+pub(crate) fn verify_client_auth(
+    session_secrets: &secrets::SessionSecrets,
+    client_public_key: &[u8; signature::ED25519_PUBLIC_KEY_LEN],
+    client_signature: &[u8; ED25519_SIGNATURE_LEN],
+) -> Result<(), error::CryptoError> {
+    let message = client_auth_signed_content(session_secrets);
+    signature::UnparsedPublicKey::new(&signature::ED25519, client_public_key)
+        .verify(&message, client_signature)
+        .map_err(|_| error::CryptoError::BadClientAuthSignature)
+}
+
+// NOTE: The challenge is bound to the session through a value derived from the session's
+// pseudorandom key, such that a signature cannot be replayed in another session.
+fn receiver_challenge_signed_content(
+    session_secrets: &secrets::SessionSecrets,
+    resource_id: &[u8],
+    challenge: &[u8; RECEIVER_CHALLENGE_LEN],
+) -> Vec<u8> {
+    let mut session_binding = [0u8; RECEIVER_CHALLENGE_LEN];
+    // SAFETY: len is not too large
+    let okm = session_secrets
+        .pseudorandom_key()
+        .expand(&[RECEIVER_CHALLENGE_LABEL], hkdf::HKDF_SHA256)
+        .unwrap();
+    // SAFETY: bytes is the correct length
+    okm.fill(&mut session_binding).unwrap();
+
+    // LAYOUT: label || session_binding || challenge || resource_id
+    [
+        RECEIVER_CHALLENGE_LABEL,
+        &session_binding,
+        challenge,
+        resource_id,
+    ]
+    .concat()
+}
+
+pub(crate) fn sign_receiver_challenge(
+    session_secrets: &secrets::SessionSecrets,
+    resource_id: &[u8],
+    challenge: &[u8; RECEIVER_CHALLENGE_LEN],
+    receiver_key_pair: &signature::Ed25519KeyPair,
+) -> [u8; ED25519_SIGNATURE_LEN] {
+    let message = receiver_challenge_signed_content(session_secrets, resource_id, challenge);
+    // SAFETY: Ed25519 signature has the correct length
+    receiver_key_pair
+        .sign(&message)
+        .as_ref()
+        .try_into()
+        .unwrap()
+}
+
+pub(crate) fn verify_receiver_challenge(
+    session_secrets: &secrets::SessionSecrets,
+    resource_id: &[u8],
+    challenge: &[u8; RECEIVER_CHALLENGE_LEN],
+    receiver_public_key: &[u8; signature::ED25519_PUBLIC_KEY_LEN],
+    receiver_signature: &[u8; ED25519_SIGNATURE_LEN],
+) -> Result<(), error::CryptoError> {
+    let message = receiver_challenge_signed_content(session_secrets, resource_id, challenge);
+    signature::UnparsedPublicKey::new(&signature::ED25519, receiver_public_key)
+        .verify(&message, receiver_signature)
+        .map_err(|_| error::CryptoError::BadReceiverChallengeSignature)
+}
+
+// The SHA-256 digest of a resource, computed incrementally as it is sent or received.
+pub(crate) struct ResourceHasher(digest::Context);
+
+impl ResourceHasher {
+    pub(crate) fn new() -> Self {
+        Self(digest::Context::new(&digest::SHA256))
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub(crate) fn finish(self) -> [u8; RESOURCE_DIGEST_LEN] {
+        // SAFETY: output has the correct length
+        self.0.finish().as_ref().try_into().unwrap()
+    }
+}
+
 fn generate_pseudorandom_key(
     own_private_key: agreement::EphemeralPrivateKey,
     other_public_key: agreement::UnparsedPublicKey<[u8; X25519_PUBLIC_KEY_LEN]>,
@@ -86,15 +308,25 @@ fn generate_pseudorandom_key(
     )
 }
 
-fn generate_master_key(prk: &hkdf::Prk, sender: &'static [u8]) -> aead::UnboundKey {
-    let mut master_key = [0u8; AEAD_KEY_LEN];
-    let info = [sender, b"master key"];
+// NOTE: Each key update derives the keys of the next generation from the same pseudorandom
+// key, such that a key does not reveal those of other generations.
+fn generate_master_key(
+    prk: &hkdf::Prk,
+    sender: &'static [u8],
+    suite: CipherSuite,
+    generation: u64,
+) -> aead::UnboundKey {
+    let algorithm = suite.algorithm();
+    let mut master_key = [0u8; MAX_AEAD_KEY_LEN];
+    let master_key = &mut master_key[..algorithm.key_len()];
+    let generation = generation.to_be_bytes();
+    let info = [sender, b"master key", &generation];
     // SAFETY: len is not too large
-    let okm = prk.expand(&info, &aead::AES_128_GCM).unwrap();
+    let okm = prk.expand(&info, algorithm).unwrap();
     // SAFETY: bytes is the correct length
-    okm.fill(&mut master_key).unwrap();
+    okm.fill(master_key).unwrap();
     // SAFETY: bytes is the correct length
-    aead::UnboundKey::new(&aead::AES_128_GCM, &master_key).unwrap()
+    aead::UnboundKey::new(algorithm, master_key).unwrap()
 }
 
 // NOTE: here `aead::NONCE_LEN` is 12
@@ -110,21 +342,26 @@ pub(crate) async fn generate_session_secrets(
     other_public_key: agreement::UnparsedPublicKey<[u8; X25519_PUBLIC_KEY_LEN]>,
     // NOTE: nonces === client_nonce || server_nonce
     nonces: [u8; 2 * NONCE_LEN],
+    suite: CipherSuite,
+    transcript: [u8; TRANSCRIPT_LEN],
     own_side: proto::Side,
 ) -> Result<secrets::SessionSecrets, error::CryptoError> {
-    let (send_side_bytes, recv_side_bytes) = match own_side {
+    let (send_side_bytes, recv_side_bytes): (&'static [u8], &'static [u8]) = match own_side {
         proto::Side::Client => (b"client", b"server"),
         proto::Side::Server => (b"server", b"client"),
     };
 
     task::spawn_blocking(move || {
         let prk = generate_pseudorandom_key(own_private_key, other_public_key, &nonces)?;
-        let send_key = generate_master_key(&prk, send_side_bytes);
-        let recv_key = generate_master_key(&prk, recv_side_bytes);
         let nonce_base = generate_nonce_base(&nonces);
 
         Ok(secrets::SessionSecrets::new(
-            prk, send_key, recv_key, nonce_base,
+            prk,
+            suite,
+            send_side_bytes,
+            recv_side_bytes,
+            nonce_base,
+            transcript,
         ))
     })
     .await
@@ -140,8 +377,329 @@ mod test {
         });
     }
 
+    #[async_std::test]
+    async fn test_sign_verify_server_hello() {
+        let sig_key_pair = generate_signature_key_pair().unwrap();
+        let sig_pub_key = signature::UnparsedPublicKey::new(
+            &signature::ED25519,
+            signature::KeyPair::public_key(&sig_key_pair)
+                .as_ref()
+                .to_owned(),
+        );
+        let (_, client_public_key) = generate_ephemeral_key_pair().unwrap();
+        let client_hello_msg = handshake::ClientHelloMessage {
+            nonce: generate_nonce().await.unwrap(),
+            public_key_bytes: client_public_key.as_ref().try_into().unwrap(),
+            versions: proto::encode_versions(proto::SUPPORTED_PROTOCOL_VERSIONS),
+            suites: suite::encode(CipherSuite::SUPPORTED),
+        };
+        let server_nonce = generate_nonce().await.unwrap();
+        let (_, public_key) = generate_ephemeral_key_pair().unwrap();
+
+        let (server_hello_msg, signed_nonces) = sign_server_hello(
+            &client_hello_msg,
+            server_nonce,
+            public_key.as_ref().try_into().unwrap(),
+            proto::CURRENT_PROTOCOL_VERSION,
+            CipherSuite::ChaCha20Poly1305,
+            &sig_key_pair,
+        );
+        let (_, verified_nonces) =
+            verify_server_hello(server_hello_msg, &client_hello_msg, &sig_pub_key).unwrap();
+        assert_eq!(signed_nonces, verified_nonces);
+
+        // The versions and cipher suites offered are covered by the signature along with those
+        // picked, such that they cannot be tampered with.
+        for other_client_hello_msg in [
+            handshake::ClientHelloMessage {
+                nonce: generate_nonce().await.unwrap(),
+                ..client_hello_msg
+            },
+            handshake::ClientHelloMessage {
+                versions: proto::encode_versions(&[]),
+                ..client_hello_msg
+            },
+            handshake::ClientHelloMessage {
+                suites: suite::encode(&[CipherSuite::Aes128Gcm]),
+                ..client_hello_msg
+            },
+        ] {
+            assert!(matches!(
+                verify_server_hello(server_hello_msg, &other_client_hello_msg, &sig_pub_key),
+                Err(error::CryptoError::BadServerHelloSignature)
+            ));
+        }
+        let downgraded_server_hello_msg = handshake::ServerHelloMessage {
+            suite: [CipherSuite::Aes128Gcm.into()],
+            ..server_hello_msg
+        };
+        assert!(matches!(
+            verify_server_hello(downgraded_server_hello_msg, &client_hello_msg, &sig_pub_key),
+            Err(error::CryptoError::BadServerHelloSignature)
+        ));
+
+        // A server hello signed by another key is reported as a key mismatch.
+        let other_sig_key_pair = generate_signature_key_pair().unwrap();
+        let (other_server_hello_msg, _) = sign_server_hello(
+            &client_hello_msg,
+            server_nonce,
+            public_key.as_ref().try_into().unwrap(),
+            proto::CURRENT_PROTOCOL_VERSION,
+            CipherSuite::ChaCha20Poly1305,
+            &other_sig_key_pair,
+        );
+        assert!(matches!(
+            verify_server_hello(other_server_hello_msg, &client_hello_msg, &sig_pub_key),
+            Err(error::CryptoError::ServerSigPubKeyMismatch)
+        ));
+        let forged_server_hello_msg = handshake::ServerHelloMessage {
+            sig_public_key_bytes: server_hello_msg.sig_public_key_bytes,
+            ..other_server_hello_msg
+        };
+        assert!(matches!(
+            verify_server_hello(forged_server_hello_msg, &client_hello_msg, &sig_pub_key),
+            Err(error::CryptoError::BadServerHelloSignature)
+        ));
+    }
+
+    async fn session_secrets_pair(
+        suite: CipherSuite,
+    ) -> (secrets::SessionSecrets, secrets::SessionSecrets) {
+        let (client_private_key, client_public_key) = generate_ephemeral_key_pair().unwrap();
+        let (server_private_key, server_public_key) = generate_ephemeral_key_pair().unwrap();
+        let nonces = [0u8; 2 * NONCE_LEN];
+        let mut transcript = [0u8; TRANSCRIPT_LEN];
+        fill_random(&mut transcript).unwrap();
+        let public_key = |public_key: agreement::PublicKey| {
+            agreement::UnparsedPublicKey::new(
+                &agreement::X25519,
+                public_key.as_ref().try_into().unwrap(),
+            )
+        };
+
+        (
+            generate_session_secrets(
+                client_private_key,
+                public_key(server_public_key),
+                nonces,
+                suite,
+                transcript,
+                proto::Side::Client,
+            )
+            .await
+            .unwrap(),
+            generate_session_secrets(
+                server_private_key,
+                public_key(client_public_key),
+                nonces,
+                suite,
+                transcript,
+                proto::Side::Server,
+            )
+            .await
+            .unwrap(),
+        )
+    }
+
+    #[async_std::test]
+    async fn test_seal_open() {
+        let payload = [b"hello world".as_slice(), &[0u8; proto::message::TAG_LEN]].concat();
+        for suite in CipherSuite::SUPPORTED {
+            let (mut client_secrets, mut server_secrets) = session_secrets_pair(*suite).await;
+            assert_eq!(client_secrets.cipher_suite(), *suite);
+
+            let sealed = client_secrets.seal(payload.clone().into()).unwrap();
+            assert_ne!(sealed.as_ref(), payload.as_slice());
+            let opened = server_secrets.open(sealed).unwrap();
+            assert_eq!(&opened[..11], b"hello world");
+        }
+
+        // Keys of different suites do not open each other's messages.
+        let (mut client_secrets, _) = session_secrets_pair(CipherSuite::Aes256Gcm).await;
+        let (_, mut server_secrets) = session_secrets_pair(CipherSuite::ChaCha20Poly1305).await;
+        let sealed = client_secrets.seal(payload.into()).unwrap();
+        assert!(server_secrets.open(sealed).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_key_update() {
+        let payload = || [b"hello world".as_slice(), &[0u8; proto::message::TAG_LEN]].concat();
+        let (mut client_secrets, mut server_secrets) =
+            session_secrets_pair(CipherSuite::Aes128Gcm).await;
+
+        assert_eq!(client_secrets.update_sealing_key().unwrap(), 1);
+        let sealed = client_secrets.seal(payload().into()).unwrap();
+        server_secrets.update_opening_key(1).unwrap();
+        assert!(server_secrets.open(sealed).is_ok());
+        assert_eq!(server_secrets.opening_generation(), 1);
+
+        // The keys of different generations do not open each other's messages.
+        assert_eq!(client_secrets.update_sealing_key().unwrap(), 2);
+        let sealed = client_secrets.seal(payload().into()).unwrap();
+        assert!(server_secrets.open(sealed).is_err());
+
+        // Only the next generation can be moved on to.
+        assert!(matches!(
+            server_secrets.update_opening_key(3),
+            Err(error::CryptoError::UnexpectedKeyGeneration(3))
+        ));
+
+        // The other direction is unaffected.
+        let sealed = server_secrets.seal(payload().into()).unwrap();
+        assert!(client_secrets.open(sealed).is_ok());
+        assert_eq!(server_secrets.sealing_generation(), 0);
+    }
+
+    #[test]
+    fn test_sealing_limit_reached() {
+        task::block_on(async {
+            let (mut secrets, _) = session_secrets_pair(CipherSuite::Aes128Gcm).await;
+            let limits = secrets::KeyUpdateLimits {
+                frames: 2,
+                bytes: 1 << 20,
+            };
+            let payload = vec![0u8; 100 + proto::message::TAG_LEN].into_boxed_slice();
+
+            secrets.seal(payload.clone()).unwrap();
+            assert!(!secrets.sealing_limit_reached(&limits));
+            secrets.seal(payload.clone()).unwrap();
+            assert!(secrets.sealing_limit_reached(&limits));
+            secrets.update_sealing_key().unwrap();
+            assert!(!secrets.sealing_limit_reached(&limits));
+
+            let limits = secrets::KeyUpdateLimits {
+                frames: 1 << 20,
+                bytes: 200,
+            };
+            secrets.seal(payload.clone()).unwrap();
+            assert!(!secrets.sealing_limit_reached(&limits));
+            secrets.seal(payload).unwrap();
+            assert!(secrets.sealing_limit_reached(&limits));
+        });
+    }
+
+    #[test]
+    fn test_key_update_limits() {
+        let limits = secrets::KeyUpdateLimits::new(2, 200).unwrap();
+        assert_eq!((limits.frames(), limits.bytes()), (2, 200));
+        for (frames, bytes) in [(0, 200), (2, 0)] {
+            assert!(matches!(
+                secrets::KeyUpdateLimits::new(frames, bytes),
+                Err(error::CryptoError::InvalidKeyUpdateLimits)
+            ));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_sign_verify_receiver_challenge() {
+        let receiver_key_pair = generate_signature_key_pair().unwrap();
+        let receiver_public_key = signature::KeyPair::public_key(&receiver_key_pair)
+            .as_ref()
+            .try_into()
+            .unwrap();
+        let (client_secrets, server_secrets) = session_secrets_pair(CipherSuite::Aes128Gcm).await;
+        let (other_secrets, _) = session_secrets_pair(CipherSuite::Aes128Gcm).await;
+        let challenge = [1u8; RECEIVER_CHALLENGE_LEN];
+
+        let receiver_signature =
+            sign_receiver_challenge(&client_secrets, b"id", &challenge, &receiver_key_pair);
+        verify_receiver_challenge(
+            &server_secrets,
+            b"id",
+            &challenge,
+            &receiver_public_key,
+            &receiver_signature,
+        )
+        .unwrap();
+
+        for (secrets, resource_id, challenge) in [
+            (&other_secrets, b"id", challenge),
+            (&server_secrets, b"ID", challenge),
+            (&server_secrets, b"id", [2u8; RECEIVER_CHALLENGE_LEN]),
+        ] {
+            assert!(matches!(
+                verify_receiver_challenge(
+                    secrets,
+                    resource_id,
+                    &challenge,
+                    &receiver_public_key,
+                    &receiver_signature,
+                ),
+                Err(error::CryptoError::BadReceiverChallengeSignature)
+            ));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_sign_verify_client_auth() {
+        let client_key_pair = generate_signature_key_pair().unwrap();
+        let client_public_key = signature::KeyPair::public_key(&client_key_pair)
+            .as_ref()
+            .try_into()
+            .unwrap();
+        let (client_secrets, server_secrets) = session_secrets_pair(CipherSuite::Aes128Gcm).await;
+        let (_, other_secrets) = session_secrets_pair(CipherSuite::Aes128Gcm).await;
+
+        let client_signature = sign_client_auth(&client_secrets, &client_key_pair);
+        verify_client_auth(&server_secrets, &client_public_key, &client_signature).unwrap();
+
+        // A signature made in another session is not accepted.
+        assert!(matches!(
+            verify_client_auth(&other_secrets, &client_public_key, &client_signature),
+            Err(error::CryptoError::BadClientAuthSignature)
+        ));
+        // Nor is one made by another key.
+        let other_public_key =
+            signature::KeyPair::public_key(&generate_signature_key_pair().unwrap())
+                .as_ref()
+                .try_into()
+                .unwrap();
+        assert!(matches!(
+            verify_client_auth(&server_secrets, &other_public_key, &client_signature),
+            Err(error::CryptoError::BadClientAuthSignature)
+        ));
+    }
+
+    #[test]
+    fn test_handshake_transcript() {
+        let client_hello_msg = handshake::ClientHelloMessage {
+            nonce: [1u8; NONCE_LEN],
+            public_key_bytes: [2u8; X25519_PUBLIC_KEY_LEN],
+            versions: proto::encode_versions(proto::SUPPORTED_PROTOCOL_VERSIONS),
+            suites: suite::encode(CipherSuite::SUPPORTED),
+        };
+        let (server_hello_msg, _) = sign_server_hello(
+            &client_hello_msg,
+            [3u8; NONCE_LEN],
+            [4u8; X25519_PUBLIC_KEY_LEN],
+            proto::CURRENT_PROTOCOL_VERSION,
+            CipherSuite::Aes128Gcm,
+            &generate_signature_key_pair().unwrap(),
+        );
+        let transcript = handshake_transcript(&client_hello_msg, &server_hello_msg);
+
+        // Any change to either hello changes the transcript.
+        let mut other_client_hello_msg = client_hello_msg;
+        other_client_hello_msg.suites = suite::encode(&[CipherSuite::Aes128Gcm]);
+        assert_ne!(
+            handshake_transcript(&other_client_hello_msg, &server_hello_msg),
+            transcript
+        );
+        let mut other_server_hello_msg = server_hello_msg;
+        other_server_hello_msg.nonce = [5u8; NONCE_LEN];
+        assert_ne!(
+            handshake_transcript(&client_hello_msg, &other_server_hello_msg),
+            transcript
+        );
+    }
+
     #[test]
     fn test_aead_key_len() {
-        assert_eq!(AEAD_KEY_LEN, aead::AES_128_GCM.key_len());
+        for suite in CipherSuite::SUPPORTED {
+            let algorithm = suite.algorithm();
+            assert!(algorithm.key_len() <= MAX_AEAD_KEY_LEN);
+            assert_eq!(algorithm.tag_len(), proto::message::TAG_LEN);
+            assert_eq!(algorithm.nonce_len(), aead::NONCE_LEN);
+        }
     }
 }
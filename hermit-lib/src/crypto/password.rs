@@ -0,0 +1,87 @@
+use std::num::NonZeroU32;
+
+use async_std::task;
+use ring::{digest, pbkdf2};
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+
+pub(crate) const SALT_LEN: usize = 16;
+pub(crate) const HASH_LEN: usize = digest::SHA256_OUTPUT_LEN;
+// SEE: https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#pbkdf2
+pub(crate) const PBKDF2_ITERATIONS: u32 = 600_000;
+
+static PBKDF2_ALGORITHM: pbkdf2::Algorithm = pbkdf2::PBKDF2_HMAC_SHA256;
+
+// A salted PBKDF2-HMAC-SHA256 verifier, such that the password itself is never stored.
+// NOTE: The iteration count is stored alongside, so that it can be raised later without
+// invalidating existing verifiers.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PasswordVerifier {
+    salt: [u8; SALT_LEN],
+    iterations: NonZeroU32,
+    hash: [u8; HASH_LEN],
+}
+
+impl PasswordVerifier {
+    pub(crate) async fn new(password: String) -> Result<Self, error::CryptoError> {
+        let mut salt = [0u8; SALT_LEN];
+        super::fill_random(&mut salt)?;
+        // SAFETY: the iteration count is non-zero
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+
+        // NOTE: Use spawn_blocking as the derivation is deliberately slow.
+        let hash = task::spawn_blocking(move || {
+            let mut hash = [0u8; HASH_LEN];
+            pbkdf2::derive(
+                PBKDF2_ALGORITHM,
+                iterations,
+                &salt,
+                password.as_bytes(),
+                &mut hash,
+            );
+            hash
+        })
+        .await;
+
+        Ok(Self {
+            salt,
+            iterations,
+            hash,
+        })
+    }
+
+    // Verify the password in constant time.
+    pub(crate) async fn verify(&self, password: String) -> bool {
+        let verifier = self.clone();
+        task::spawn_blocking(move || {
+            pbkdf2::verify(
+                PBKDF2_ALGORITHM,
+                verifier.iterations,
+                &verifier.salt,
+                password.as_bytes(),
+                &verifier.hash,
+            )
+            .is_ok()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_password_verifier() {
+        let verifier = PasswordVerifier::new("password".to_owned()).await.unwrap();
+        assert!(verifier.verify("password".to_owned()).await);
+        assert!(!verifier.verify("Password".to_owned()).await);
+        assert!(!verifier.verify("".to_owned()).await);
+
+        // The same password is salted differently each time.
+        let other = PasswordVerifier::new("password".to_owned()).await.unwrap();
+        assert_ne!(verifier.salt, other.salt);
+        assert_ne!(verifier.hash, other.hash);
+    }
+}
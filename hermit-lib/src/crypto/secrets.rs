@@ -1,6 +1,7 @@
 use ring::aead::{self, BoundKey};
 use ring::hkdf;
 
+use super::CipherSuite;
 use crate::error;
 use crate::proto::message::{Message, PlainMessageType, TAG_LEN};
 
@@ -27,30 +28,93 @@ impl NonceSequence {
 }
 
 impl aead::NonceSequence for NonceSequence {
+    // NOTE: Refuse to go on rather than let the counter wrap, which would reuse a nonce.
     fn advance(&mut self) -> Result<aead::Nonce, ring::error::Unspecified> {
         let nonce = Self::xor(&self.base, self.counter);
-        self.counter += 1;
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(ring::error::Unspecified)?;
         Ok(aead::Nonce::assume_unique_for_key(nonce))
     }
 }
 
+// The amount of data sealed under a key after which it is updated, keeping well within the
+// safe usage limits of the AEAD algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUpdateLimits {
+    pub(crate) frames: u64,
+    pub(crate) bytes: u64,
+}
+
+impl KeyUpdateLimits {
+    // NOTE: Both limits must be positive, as a key is updated before sealing any frame once
+    // either of them is reached.
+    pub fn new(frames: u64, bytes: u64) -> Result<Self, error::CryptoError> {
+        if frames == 0 || bytes == 0 {
+            return Err(error::CryptoError::InvalidKeyUpdateLimits);
+        }
+        Ok(Self { frames, bytes })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Default for KeyUpdateLimits {
+    fn default() -> Self {
+        Self {
+            frames: 1 << 24,
+            bytes: 1 << 34,
+        }
+    }
+}
+
 // TODO: use `secrecy` and `zeroize` to secure the secrets
 pub struct SessionSecrets {
-    // NOTE: kept for potential key generations
+    // NOTE: kept for key updates
     pseudorandom_key: hkdf::Prk,
+    cipher_suite: CipherSuite,
+    // The labels from which the keys of the sending and receiving sides are derived.
+    sealing_label: &'static [u8],
+    opening_label: &'static [u8],
+    nonce_base: [u8; aead::NONCE_LEN],
+    // The digest of the handshake, to which signatures made later in the session are bound.
+    transcript: [u8; super::TRANSCRIPT_LEN],
     sealing_key: aead::SealingKey<NonceSequence>,
     opening_key: aead::OpeningKey<NonceSequence>,
+    sealing_generation: u64,
+    opening_generation: u64,
+    // The frames and bytes sealed under the current sealing key.
+    sealed_frames: u64,
+    sealed_bytes: u64,
 }
 
 impl SessionSecrets {
     pub(super) fn new(
         pseudorandom_key: hkdf::Prk,
-        sealing_key: aead::UnboundKey,
-        opening_key: aead::UnboundKey,
+        cipher_suite: CipherSuite,
+        sealing_label: &'static [u8],
+        opening_label: &'static [u8],
         nonce_base: [u8; aead::NONCE_LEN],
+        transcript: [u8; super::TRANSCRIPT_LEN],
     ) -> Self {
+        let sealing_key =
+            super::generate_master_key(&pseudorandom_key, sealing_label, cipher_suite, 0);
+        let opening_key =
+            super::generate_master_key(&pseudorandom_key, opening_label, cipher_suite, 0);
         Self {
             pseudorandom_key,
+            cipher_suite,
+            sealing_label,
+            opening_label,
+            nonce_base,
+            transcript,
             sealing_key: aead::SealingKey::<NonceSequence>::new(
                 sealing_key,
                 NonceSequence::new(&nonce_base),
@@ -59,6 +123,10 @@ impl SessionSecrets {
                 opening_key,
                 NonceSequence::new(&nonce_base),
             ),
+            sealing_generation: 0,
+            opening_generation: 0,
+            sealed_frames: 0,
+            sealed_bytes: 0,
         }
     }
 
@@ -66,12 +134,70 @@ impl SessionSecrets {
         &self.pseudorandom_key
     }
 
+    pub(crate) fn transcript(&self) -> &[u8; super::TRANSCRIPT_LEN] {
+        &self.transcript
+    }
+
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    pub fn sealing_generation(&self) -> u64 {
+        self.sealing_generation
+    }
+
+    pub fn opening_generation(&self) -> u64 {
+        self.opening_generation
+    }
+
+    // Whether enough has been sealed under the current sealing key for it to be updated.
+    pub(crate) fn sealing_limit_reached(&self, limits: &KeyUpdateLimits) -> bool {
+        self.sealed_frames >= limits.frames || self.sealed_bytes >= limits.bytes
+    }
+
+    // Move on to the next generation of the sealing key, returning its generation.
+    pub(crate) fn update_sealing_key(&mut self) -> Result<u64, error::CryptoError> {
+        let generation = self
+            .sealing_generation
+            .checked_add(1)
+            .ok_or(error::CryptoError::KeyGenerationExhausted)?;
+        let key = super::generate_master_key(
+            &self.pseudorandom_key,
+            self.sealing_label,
+            self.cipher_suite,
+            generation,
+        );
+        self.sealing_key = aead::SealingKey::new(key, NonceSequence::new(&self.nonce_base));
+        self.sealing_generation = generation;
+        self.sealed_frames = 0;
+        self.sealed_bytes = 0;
+        Ok(generation)
+    }
+
+    // Move on to the next generation of the opening key, which the peer has announced.
+    pub(crate) fn update_opening_key(&mut self, generation: u64) -> Result<(), error::CryptoError> {
+        if self.opening_generation.checked_add(1) != Some(generation) {
+            return Err(error::CryptoError::UnexpectedKeyGeneration(generation));
+        }
+        let key = super::generate_master_key(
+            &self.pseudorandom_key,
+            self.opening_label,
+            self.cipher_suite,
+            generation,
+        );
+        self.opening_key = aead::OpeningKey::new(key, NonceSequence::new(&self.nonce_base));
+        self.opening_generation = generation;
+        Ok(())
+    }
+
     pub(crate) fn seal(&mut self, mut payload: Box<[u8]>) -> Result<Message, error::CryptoError> {
         let len = payload.len();
         let tag = self
             .sealing_key
             .seal_in_place_separate_tag(aead::Aad::empty(), payload[..len - TAG_LEN].as_mut())?;
         payload[len - TAG_LEN..].copy_from_slice(tag.as_ref());
+        self.sealed_frames += 1;
+        self.sealed_bytes = self.sealed_bytes.saturating_add(len as u64);
         Ok(Message::new(PlainMessageType::Secure, payload))
     }
     pub(crate) fn open(&mut self, mut message: Message) -> Result<Box<[u8]>, error::CryptoError> {
@@ -80,3 +206,19 @@ impl SessionSecrets {
         Ok(message.into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ring::aead::NonceSequence as _;
+
+    use super::*;
+
+    #[test]
+    fn test_nonce_sequence_does_not_wrap() {
+        let mut nonces = NonceSequence::new(&[0u8; aead::NONCE_LEN]);
+        nonces.counter = u64::MAX - 1;
+        assert!(nonces.advance().is_ok());
+        assert!(nonces.advance().is_err());
+        assert!(nonces.advance().is_err());
+    }
+}
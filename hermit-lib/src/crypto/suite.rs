@@ -0,0 +1,112 @@
+use std::fmt;
+use std::str::FromStr;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use ring::aead;
+
+use crate::error;
+
+// The number of cipher suites a client can offer, padded with the reserved 0x00.
+pub(crate) const CIPHER_SUITES_LEN: usize = 4;
+
+// NEGOTIATION:
+// The client offers the cipher suites it allows in order of preference in its hello. The
+// server picks the first one it also allows, and confirms it in its signed hello.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum CipherSuite {
+    // NOTE: 0x00 RESERVED
+    Aes128Gcm = 0x01,
+    Aes256Gcm = 0x02,
+    ChaCha20Poly1305 = 0x03,
+}
+
+impl CipherSuite {
+    pub const SUPPORTED: &'static [Self] =
+        &[Self::Aes128Gcm, Self::Aes256Gcm, Self::ChaCha20Poly1305];
+
+    pub(crate) fn algorithm(self) -> &'static aead::Algorithm {
+        match self {
+            Self::Aes128Gcm => &aead::AES_128_GCM,
+            Self::Aes256Gcm => &aead::AES_256_GCM,
+            Self::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Aes128Gcm => "aes-128-gcm",
+            Self::Aes256Gcm => "aes-256-gcm",
+            Self::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+}
+
+impl fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CipherSuite {
+    type Err = error::CryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|suite| suite.name() == s)
+            .ok_or_else(|| error::CryptoError::UnknownCipherSuite(s.to_owned()))
+    }
+}
+
+pub(crate) fn encode(suites: &[CipherSuite]) -> [u8; CIPHER_SUITES_LEN] {
+    let mut encoded = [0u8; CIPHER_SUITES_LEN];
+    for (byte, suite) in encoded.iter_mut().zip(suites) {
+        *byte = (*suite).into();
+    }
+    encoded
+}
+
+// Pick the first suite offered which is allowed, skipping the unknown ones.
+pub(crate) fn negotiate(
+    offered: &[u8; CIPHER_SUITES_LEN],
+    allowed: &[CipherSuite],
+) -> Option<CipherSuite> {
+    offered
+        .iter()
+        .filter_map(|suite| CipherSuite::try_from(*suite).ok())
+        .find(|suite| allowed.contains(suite))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let offered = encode(&[CipherSuite::ChaCha20Poly1305, CipherSuite::Aes256Gcm]);
+        assert_eq!(
+            negotiate(&offered, CipherSuite::SUPPORTED),
+            Some(CipherSuite::ChaCha20Poly1305)
+        );
+        assert_eq!(
+            negotiate(&offered, &[CipherSuite::Aes128Gcm, CipherSuite::Aes256Gcm]),
+            Some(CipherSuite::Aes256Gcm)
+        );
+        assert_eq!(negotiate(&offered, &[CipherSuite::Aes128Gcm]), None);
+        assert_eq!(
+            negotiate(&[0xff, 0x00, 0x00, 0x00], CipherSuite::SUPPORTED),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        for suite in CipherSuite::SUPPORTED {
+            assert_eq!(suite.to_string().parse::<CipherSuite>().unwrap(), *suite);
+        }
+        assert!("aes-512-gcm".parse::<CipherSuite>().is_err());
+    }
+}
@@ -1,8 +1,12 @@
 use ring;
 use thiserror;
 
-use crate::proto::message::PlainMessageType;
+use crate::crypto::CipherSuite;
+use crate::proto::message::handshake::HandshakeFailure;
+use crate::proto::message::transfer::Compression;
+use crate::proto::message::{PlainMessageType, SecureMessageType};
 use crate::proto::ProtocolVersion;
+use crate::server::DownloadState;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -10,10 +14,30 @@ pub enum Error {
     Crypto(#[from] CryptoError),
     #[error("Error in IO/Network: {0}")]
     IONetwork(#[from] async_std::io::Error),
+    #[error("Error connecting over QUIC: {0}")]
+    QuicConnect(#[from] quinn::ConnectError),
+    #[error("Error in QUIC connection: {0}")]
+    QuicConnection(#[from] quinn::ConnectionError),
+    #[error("Error in TLS configuration: {0}")]
+    Tls(#[from] rustls::Error),
     #[error("Error parsing message: {0}")]
     MessageParsing(#[from] InvalidMessageError),
     #[error("Error in message length limit adjustment: {0}")]
     LenLimitAdjustment(#[from] LenLimitAdjustmentError),
+    #[error("Resource request rejected: {0}")]
+    ResourceRequest(#[from] ResourceRequestError),
+    #[error("Error in resource transfer: {0}")]
+    Transfer(#[from] TransferError),
+    #[error("Error in resource storage: {0}")]
+    Storage(#[from] StorageError),
+    #[error("Invalid resource ID: {0}")]
+    ResourceId(#[from] ResourceIdError),
+    #[error("No common protocol version with the peer")]
+    NoCommonVersion,
+    #[error("No common cipher suite with the peer")]
+    NoCommonCipherSuite,
+    #[error("Client authentication rejected: {0}")]
+    ClientAuth(#[from] ClientAuthError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -24,8 +48,24 @@ pub enum CryptoError {
     KeyRejected(&'static str),
     #[error("Bad server hello signature")]
     BadServerHelloSignature,
+    #[error(
+        "Server hello signed by a different key than expected; the server's identity has changed"
+    )]
+    ServerSigPubKeyMismatch,
     #[error("Bad server public key")]
     BadServerPublicKey,
+    #[error("Bad receiver challenge signature")]
+    BadReceiverChallengeSignature,
+    #[error("Bad client authentication signature")]
+    BadClientAuthSignature,
+    #[error("Unknown cipher suite: {0}")]
+    UnknownCipherSuite(String),
+    #[error("Key generations exhausted")]
+    KeyGenerationExhausted,
+    #[error("Unexpected key generation: {0}")]
+    UnexpectedKeyGeneration(u64),
+    #[error("Key update limits must be positive")]
+    InvalidKeyUpdateLimits,
 }
 
 impl From<ring::error::Unspecified> for CryptoError {
@@ -44,8 +84,22 @@ impl From<ring::error::KeyRejected> for CryptoError {
 pub enum InvalidMessageError {
     #[error("Invalid message type: {0}")]
     MessageType(#[from] num_enum::TryFromPrimitiveError<PlainMessageType>),
+    #[error("Unexpected message type: {0:?}")]
+    UnexpectedMessageType(PlainMessageType),
+    #[error("Invalid secure message type: {0}")]
+    SecureMessageType(#[from] num_enum::TryFromPrimitiveError<SecureMessageType>),
+    #[error("Unexpected secure message type: {0:?}")]
+    UnexpectedSecureMessageType(SecureMessageType),
     #[error("Invalid protocol version: {0}")]
     ProtocolVersion(#[from] num_enum::TryFromPrimitiveError<ProtocolVersion>),
+    #[error("Unexpected protocol version: {0:?}")]
+    UnexpectedProtocolVersion(ProtocolVersion),
+    #[error("Invalid handshake failure: {0}")]
+    HandshakeFailure(#[from] num_enum::TryFromPrimitiveError<HandshakeFailure>),
+    #[error("Unexpected cipher suite: {0}")]
+    UnexpectedCipherSuite(CipherSuite),
+    #[error("Invalid cipher suite: {0}")]
+    CipherSuite(#[from] num_enum::TryFromPrimitiveError<CipherSuite>),
     #[error("Payload length out of valid range; length {length}")]
     PayloadLengthOutOfRange { length: usize },
     #[error("Payload length above limit; length {length}, limit {limit}")]
@@ -56,6 +110,10 @@ pub enum InvalidMessageError {
     CborDeserialization(String),
     #[error("CBOR serialization error: {0}")]
     CborSerialization(String),
+    #[error("Invalid compressed frame")]
+    CompressedFrame,
+    #[error("Secure message too long; length {length}")]
+    SecureMessageTooLong { length: usize },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -66,4 +124,78 @@ pub enum LenLimitAdjustmentError {
     InvalidLimit(usize),
     #[error("No ongoing length limit request to receive a response for.")]
     NoOngoingRequest,
-}
\ No newline at end of file
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ResourceRequestError {
+    #[error("Invalid receiver control")]
+    InvalidReceiverControl,
+    #[error("Invalid expiry duration")]
+    InvalidExpiry,
+    #[error("Resource too large")]
+    ResourceTooLarge,
+    #[error("Invalid resource manifest")]
+    InvalidManifest,
+    #[error("Resource cannot be received")]
+    Failed,
+    #[error("Invalid resource offsets")]
+    InvalidOffsets,
+    #[error("Receiver key pair required to answer the challenge")]
+    MissingReceiverKeyPair,
+    #[error("Compression not offered: {0:?}")]
+    UnexpectedCompression(Compression),
+    #[error("Client not authorized to send resources")]
+    Unauthorized,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientAuthError {
+    #[error("Bad signature")]
+    BadSignature,
+    #[error("Client key not allowed by the server")]
+    NotAllowed,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TransferError {
+    #[error("Resource size mismatch; declared {declared}, transferred {transferred}")]
+    ResourceSizeMismatch { declared: u64, transferred: u64 },
+    #[error("No remaining resource to be transferred")]
+    NoRemainingResource,
+    #[error("Resources remaining to be transferred: {0}")]
+    RemainingResources(usize),
+    #[error("Invalid resource path: {0:?}")]
+    InvalidResourcePath(String),
+    #[error("Duplicate resource path: {0:?}")]
+    DuplicateResourcePath(String),
+    #[error("Resource path is nested under a file: {0:?}")]
+    NestedResourcePath(String),
+    #[error("Resource digest mismatch; the data received differs from the data sent")]
+    DigestMismatch,
+    #[error("Total size of the resources overflows")]
+    ManifestSizeOverflow,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageError {
+    #[error("Resource not found")]
+    ResourceNotFound,
+    #[error("Resource already exists")]
+    DuplicateResource,
+    #[error("Invalid resource state: {0:?}")]
+    InvalidState(DownloadState),
+    #[error("Metadata serialization error: {0}")]
+    MetadataSerialization(String),
+    #[error("Metadata deserialization error: {0}")]
+    MetadataDeserialization(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResourceIdError {
+    #[error("Empty resource ID")]
+    Empty,
+    #[error("Unknown word in resource ID: {0}")]
+    UnknownWord(String),
+    #[error("Invalid resource ID length: {0}")]
+    InvalidLength(usize),
+}
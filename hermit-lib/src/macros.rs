@@ -82,6 +82,15 @@ macro_rules! plain_msg {
     };
 }
 
+#[macro_export]
+macro_rules! secure_msg {
+    ($message:ty, $message_type:expr) => {
+        impl $crate::proto::message::Secure for $message {
+            const MESSAGE_TYPE: $crate::proto::message::SecureMessageType = $message_type;
+        }
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! nil {
@@ -3,8 +3,11 @@ pub(crate) const MAX_LEN_LIMIT: usize = (1 << 15) - 1;
 
 pub(crate) use crate::proto::plain::header::{MessageHeader, PlainMessageType};
 pub(crate) use crate::proto::plain::message::Message;
-pub(crate) use crate::proto::secure::message::{SecureMessageType, TAG_LEN};
+pub(crate) use crate::proto::secure::message::{recv_header, Secure, SecureMessageType, TAG_LEN};
 
 pub(crate) use crate::proto::plain::handshake;
+pub(crate) use crate::proto::plain::key_update;
 pub(crate) use crate::proto::plain::len_limit;
-pub(crate) use crate::proto::secure::transfer;
+pub(crate) use crate::proto::secure::auth;
+pub(crate) use crate::proto::secure::compression;
+pub use crate::proto::secure::transfer;
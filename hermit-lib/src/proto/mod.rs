@@ -1,13 +1,21 @@
-pub(crate) mod message;
+pub mod message;
 mod plain;
 mod secure;
-pub(crate) mod stream;
+pub mod stream;
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use serde::{Deserialize, Serialize};
 
+// NOTE: The headers of the handshake carry this version, as no version has been negotiated
+// yet, so every later version must still be able to parse them.
 pub static CURRENT_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V0_1;
 
+// The versions supported, in order of preference.
+pub static SUPPORTED_PROTOCOL_VERSIONS: &[ProtocolVersion] = &[ProtocolVersion::V0_1];
+
+// The number of versions a client can offer, padded with the reserved 0x00.
+pub(crate) const PROTOCOL_VERSIONS_LEN: usize = 4;
+
 #[repr(u8)]
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Deserialize, Serialize,
@@ -17,8 +25,42 @@ pub enum ProtocolVersion {
     V0_1 = 0x01,
 }
 
+pub(crate) fn encode_versions(versions: &[ProtocolVersion]) -> [u8; PROTOCOL_VERSIONS_LEN] {
+    let mut encoded = [0u8; PROTOCOL_VERSIONS_LEN];
+    for (byte, version) in encoded.iter_mut().zip(versions) {
+        *byte = (*version).into();
+    }
+    encoded
+}
+
+// Pick the first version offered which is supported, skipping the unknown ones.
+pub(crate) fn negotiate_version(offered: &[u8; PROTOCOL_VERSIONS_LEN]) -> Option<ProtocolVersion> {
+    offered
+        .iter()
+        .filter_map(|version| ProtocolVersion::try_from(*version).ok())
+        .find(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum Side {
     Client,
     Server,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_version() {
+        let offered = encode_versions(SUPPORTED_PROTOCOL_VERSIONS);
+        assert_eq!(negotiate_version(&offered), Some(ProtocolVersion::V0_1));
+        // Unknown versions are skipped.
+        assert_eq!(
+            negotiate_version(&[0xff, 0x01, 0x00, 0x00]),
+            Some(ProtocolVersion::V0_1)
+        );
+        assert_eq!(negotiate_version(&encode_versions(&[])), None);
+        assert_eq!(negotiate_version(&[0xff, 0xfe, 0x00, 0x00]), None);
+    }
+}
@@ -1,32 +1,57 @@
-use super::header::PlainMessageType;
-use crate::{crypto, plain_msg};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-pub(crate) const CLIENT_HELLO_MSG_LEN: usize = crypto::NONCE_LEN + crypto::X25519_PUBLIC_KEY_LEN;
-pub(crate) const SERVER_HELLO_MSG_LEN: usize =
-    crypto::NONCE_LEN + crypto::X25519_PUBLIC_KEY_LEN + crypto::ED25519_SIGNATURE_LEN;
+use super::header::PlainMessageType;
+use crate::crypto::suite::CIPHER_SUITES_LEN;
+use crate::proto::PROTOCOL_VERSIONS_LEN;
+use crate::{crypto, error, plain_msg};
+
+pub(crate) const CLIENT_HELLO_MSG_LEN: usize =
+    crypto::NONCE_LEN + crypto::X25519_PUBLIC_KEY_LEN + PROTOCOL_VERSIONS_LEN + CIPHER_SUITES_LEN;
+pub(crate) const SERVER_HELLO_MSG_LEN: usize = crypto::NONCE_LEN
+    + crypto::X25519_PUBLIC_KEY_LEN
+    + crypto::ED25519_PUBLIC_KEY_LEN
+    + crypto::ED25519_SIGNATURE_LEN
+    + 2;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ClientHelloMessage {
     pub(crate) nonce: [u8; crypto::NONCE_LEN],
     pub(crate) public_key_bytes: [u8; crypto::X25519_PUBLIC_KEY_LEN],
+    // The protocol versions supported by the client, in order of preference and padded with
+    // the reserved 0x00.
+    pub(crate) versions: [u8; PROTOCOL_VERSIONS_LEN],
+    // The cipher suites allowed by the client, likewise.
+    pub(crate) suites: [u8; CIPHER_SUITES_LEN],
 }
 
 plain_msg!(ClientHelloMessage, PlainMessageType::ClientHello, CLIENT_HELLO_MSG_LEN =>
     nonce, crypto::NONCE_LEN;
-    public_key_bytes, crypto::X25519_PUBLIC_KEY_LEN
+    public_key_bytes, crypto::X25519_PUBLIC_KEY_LEN;
+    versions, PROTOCOL_VERSIONS_LEN;
+    suites, CIPHER_SUITES_LEN
 );
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ServerHelloMessage {
     pub(crate) nonce: [u8; crypto::NONCE_LEN],
     pub(crate) public_key_bytes: [u8; crypto::X25519_PUBLIC_KEY_LEN],
+    // The server's signature public key, such that a client without a pre-shared key can
+    // trust it on first use.
+    pub(crate) sig_public_key_bytes: [u8; crypto::ED25519_PUBLIC_KEY_LEN],
     pub(crate) signature: [u8; crypto::ED25519_SIGNATURE_LEN],
+    // The protocol version picked by the server among those offered.
+    pub(crate) version: [u8; 1],
+    // The cipher suite picked by the server among those offered.
+    pub(crate) suite: [u8; 1],
 }
 
 plain_msg!(ServerHelloMessage, PlainMessageType::ServerHello, SERVER_HELLO_MSG_LEN =>
     nonce, crypto::NONCE_LEN;
     public_key_bytes, crypto::X25519_PUBLIC_KEY_LEN;
-    signature, crypto::ED25519_SIGNATURE_LEN
+    sig_public_key_bytes, crypto::ED25519_PUBLIC_KEY_LEN;
+    signature, crypto::ED25519_SIGNATURE_LEN;
+    version, 1;
+    suite, 1
 );
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,10 +64,56 @@ pub(crate) struct DowngradeMessage;
 
 plain_msg!(DowngradeMessage, PlainMessageType::Downgrade);
 
+// The reason for which the server aborts the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum HandshakeFailure {
+    NoCommonVersion = 0x01,
+    NoCommonCipherSuite = 0x02,
+}
+
+impl From<HandshakeFailure> for error::Error {
+    fn from(value: HandshakeFailure) -> Self {
+        match value {
+            HandshakeFailure::NoCommonVersion => Self::NoCommonVersion,
+            HandshakeFailure::NoCommonCipherSuite => Self::NoCommonCipherSuite,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HandshakeFailureMessage {
+    pub(crate) reason: [u8; 1],
+}
+
+plain_msg!(HandshakeFailureMessage, PlainMessageType::HandshakeFailure, 1 =>
+    reason, 1
+);
+
+impl From<HandshakeFailure> for HandshakeFailureMessage {
+    fn from(value: HandshakeFailure) -> Self {
+        Self {
+            reason: [value.into()],
+        }
+    }
+}
+
+impl TryFrom<HandshakeFailureMessage> for HandshakeFailure {
+    type Error = error::InvalidMessageError;
+
+    fn try_from(value: HandshakeFailureMessage) -> Result<Self, Self::Error> {
+        Ok(Self::try_from(value.reason[0])?)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use ring::signature::KeyPair;
+
     use super::super::message::Message;
     use super::*;
+    use crate::proto::{encode_versions, CURRENT_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
 
     #[async_std::test]
     async fn test_client_hello_message() {
@@ -54,6 +125,8 @@ mod test {
                 .as_ref()
                 .try_into()
                 .unwrap(),
+            versions: encode_versions(SUPPORTED_PROTOCOL_VERSIONS),
+            suites: crypto::suite::encode(crypto::CipherSuite::SUPPORTED),
         };
         let test_message = Message::from(test);
         let test_from_message = ClientHelloMessage::try_from(test_message).unwrap();
@@ -82,11 +155,14 @@ mod test {
         let test = ServerHelloMessage {
             nonce,
             public_key_bytes,
+            sig_public_key_bytes: sig_key_pair.public_key().as_ref().try_into().unwrap(),
             signature: sig_key_pair
                 .sign(&sig_content_bytes)
                 .as_ref()
                 .try_into()
                 .unwrap(),
+            version: [CURRENT_PROTOCOL_VERSION.into()],
+            suite: [crypto::CipherSuite::Aes128Gcm.into()],
         };
 
         let test_message = Message::from(test);
@@ -101,4 +177,15 @@ mod test {
         let msg_back = DisconnectMessage::try_from(msg_from).unwrap();
         assert_eq!(msg, msg_back);
     }
+
+    #[test]
+    fn test_handshake_failure_message() {
+        let msg = HandshakeFailureMessage::from(HandshakeFailure::NoCommonCipherSuite);
+        let msg_back = HandshakeFailureMessage::try_from(Message::from(msg)).unwrap();
+        assert_eq!(
+            HandshakeFailure::try_from(msg_back).unwrap(),
+            HandshakeFailure::NoCommonCipherSuite
+        );
+        assert!(HandshakeFailure::try_from(HandshakeFailureMessage { reason: [0xff] }).is_err());
+    }
 }
@@ -14,9 +14,12 @@ pub enum PlainMessageType {
     ServerHello = 0x02,
     Disconnect = 0x03,
     Downgrade = 0x04,
+    HandshakeFailure = 0x05,
 
     AdjustLenLimitRequest = 0x10,
     AdjustLenLimitResponse = 0x11,
+
+    KeyUpdate = 0x20,
 }
 
 // LAYOUT:
@@ -58,6 +61,10 @@ impl MessageHeader {
         self.version
     }
 
+    pub(super) fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = version;
+    }
+
     pub(super) fn length(&self) -> usize {
         self.length
     }
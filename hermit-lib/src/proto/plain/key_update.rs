@@ -0,0 +1,47 @@
+use super::header::PlainMessageType;
+use crate::plain_msg;
+
+// Announce that the sender has moved on to the given generation of its sealing key, and
+// whether the receiver should then move on to the next generation of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyUpdate {
+    generation: [u8; 8],
+    update_requested: [u8; 1],
+}
+
+impl KeyUpdate {
+    pub(crate) fn new(generation: u64, update_requested: bool) -> Self {
+        Self {
+            generation: generation.to_be_bytes(),
+            update_requested: if update_requested { [1] } else { [0] },
+        }
+    }
+
+    pub(crate) fn generation(self) -> u64 {
+        u64::from_be_bytes(self.generation)
+    }
+
+    pub(crate) fn update_requested(self) -> bool {
+        self.update_requested[0] == 1
+    }
+}
+
+plain_msg!(KeyUpdate, PlainMessageType::KeyUpdate, 9 =>
+    generation, 8;
+    update_requested, 1
+);
+
+#[cfg(test)]
+mod test {
+    use super::super::message::Message;
+    use super::*;
+
+    #[test]
+    fn test_key_update() {
+        let msg = KeyUpdate::new(u64::MAX, true);
+        let msg_back = KeyUpdate::try_from(Message::from(msg)).unwrap();
+        assert_eq!(msg, msg_back);
+        assert_eq!(msg_back.generation(), u64::MAX);
+        assert!(msg_back.update_requested());
+    }
+}
@@ -1,5 +1,6 @@
 use super::header::{MessageHeader, PlainMessageType, MSG_HEADER_LEN};
-use crate::{error, proto::CURRENT_PROTOCOL_VERSION};
+use crate::error;
+use crate::proto::{ProtocolVersion, CURRENT_PROTOCOL_VERSION};
 
 pub struct Message {
     header: MessageHeader,
@@ -29,6 +30,14 @@ impl Message {
     pub(in crate::proto) fn header(&self) -> MessageHeader {
         self.header
     }
+
+    pub(super) fn set_version(&mut self, version: ProtocolVersion) {
+        self.header.set_version(version);
+    }
+
+    pub(crate) fn plain_msg_type(&self) -> PlainMessageType {
+        self.header.plain_msg_type()
+    }
 }
 
 impl AsRef<[u8]> for Message {
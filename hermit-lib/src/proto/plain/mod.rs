@@ -1,5 +1,6 @@
 pub(crate) mod handshake;
 pub(crate) mod header;
+pub(crate) mod key_update;
 pub(crate) mod len_limit;
 pub(crate) mod message;
 pub(crate) mod stream;
@@ -5,6 +5,7 @@ use super::message::Message;
 use crate::error;
 use crate::proto::message::{MAX_LEN_LIMIT, MIN_LEN_LIMIT};
 use crate::proto::stream::BaseStream;
+use crate::proto::ProtocolVersion;
 
 #[async_trait::async_trait]
 pub trait Plain {
@@ -17,6 +18,8 @@ pub struct PlainStream {
     stream: BaseStream,
     len_limit: usize,
     header_buffer: [u8; MSG_HEADER_LEN],
+    // The version negotiated in the handshake, which every later header must carry.
+    version: Option<ProtocolVersion>,
 }
 
 impl From<BaseStream> for PlainStream {
@@ -25,6 +28,7 @@ impl From<BaseStream> for PlainStream {
             stream: value,
             len_limit: MIN_LEN_LIMIT,
             header_buffer: [0u8; MSG_HEADER_LEN],
+            version: None,
         }
     }
 }
@@ -34,6 +38,10 @@ impl PlainStream {
         self.len_limit
     }
 
+    pub(crate) fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = Some(version);
+    }
+
     // SANITY CHECK
     #[cfg(debug_assertions)]
     fn send_check(&self, msg: &Message) -> Result<(), error::InvalidMessageError> {
@@ -62,7 +70,10 @@ impl Plain for PlainStream {
         self.len_limit = len_limit.clamp(MIN_LEN_LIMIT, MAX_LEN_LIMIT);
     }
 
-    async fn send(&mut self, msg: Message) -> Result<(), error::Error> {
+    async fn send(&mut self, mut msg: Message) -> Result<(), error::Error> {
+        if let Some(version) = self.version {
+            msg.set_version(version);
+        }
         self.stream
             .write_all(&<[u8; MSG_HEADER_LEN]>::from(msg.header()))
             .await?;
@@ -74,6 +85,14 @@ impl Plain for PlainStream {
     async fn recv(&mut self) -> Result<Message, error::Error> {
         self.stream.read_exact(&mut self.header_buffer).await?;
         let mut message = Message::raw(&self.header_buffer)?;
+        if let Some(version) = self.version {
+            if message.header().version() != version {
+                return Err(error::InvalidMessageError::UnexpectedProtocolVersion(
+                    message.header().version(),
+                )
+                .into());
+            }
+        }
         self.stream.read_exact(message.as_mut()).await?;
         Ok(message)
     }
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use super::message::SecureMessageType;
+use crate::{crypto, secure_msg};
+
+// CLIENT AUTHENTICATION:
+// Right after the handshake, a client may prove its identity with a `ClientAuthRequest`,
+// signing the handshake transcript with its own signature key pair. The server verifies the
+// signature and checks the public key against its allowlist, if any, before recording the
+// client's identity for the rest of the session.
+
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ClientAuthRequest {
+    #[serde_as(as = "serde_with::Bytes")]
+    pub public_key: [u8; crypto::ED25519_PUBLIC_KEY_LEN],
+    #[serde_as(as = "serde_with::Bytes")]
+    pub signature: [u8; crypto::ED25519_SIGNATURE_LEN],
+}
+
+secure_msg!(ClientAuthRequest, SecureMessageType::ClientAuthRequest);
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClientAuthResponse {
+    Ok,
+    BadSignature,
+    NotAllowed,
+}
+
+secure_msg!(ClientAuthResponse, SecureMessageType::ClientAuthResponse);
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use super::message::TAG_LEN;
+use crate::error;
+use crate::proto::message::MAX_LEN_LIMIT;
+
+// NEGOTIATION:
+// The client offers the compressions it supports in order of preference in its transfer
+// request, or none to disable compression for the transfer. The server picks the first one
+// it supports in its response, after which the data phase and the final `ResourceAck` are
+// compressed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    Lz4,
+}
+
+impl Compression {
+    pub const SUPPORTED: &'static [Self] = &[Self::Lz4];
+}
+
+pub(crate) fn negotiate(offered: &[Compression]) -> Option<Compression> {
+    offered
+        .iter()
+        .copied()
+        .find(|compression| Compression::SUPPORTED.contains(compression))
+}
+
+// LAYOUT: flag (1 byte) || frame data, compressed if the flag is set
+// Frames which do not shrink are sent as is, so a frame grows by at most the flag.
+pub(crate) const FRAME_FLAG_LEN: usize = 1;
+
+const FRAME_RAW: u8 = 0x00;
+const FRAME_COMPRESSED: u8 = 0x01;
+
+// The largest frame data, bounding the decompressed length of a frame.
+const MAX_FRAME_DATA_LEN: usize = MAX_LEN_LIMIT - TAG_LEN - FRAME_FLAG_LEN;
+
+// NOTE: As with sealing, TAG_LEN of space is reserved at the end of the payload, both
+// before and after compression.
+pub(crate) fn compress(compression: Compression, payload: &[u8]) -> Box<[u8]> {
+    let data = &payload[..payload.len() - TAG_LEN];
+    let compressed = match compression {
+        Compression::Lz4 => lz4_flex::block::compress(data),
+    };
+
+    let (flag, data) = match compressed.len() < data.len() {
+        true => (FRAME_COMPRESSED, compressed.as_slice()),
+        false => (FRAME_RAW, data),
+    };
+    let mut frame = Vec::with_capacity(FRAME_FLAG_LEN + data.len() + TAG_LEN);
+    frame.push(flag);
+    frame.extend_from_slice(data);
+    frame.resize(frame.capacity(), 0);
+    frame.into_boxed_slice()
+}
+
+pub(crate) fn decompress(
+    compression: Compression,
+    payload: &[u8],
+) -> Result<Box<[u8]>, error::InvalidMessageError> {
+    if payload.len() < FRAME_FLAG_LEN + TAG_LEN {
+        return Err(error::InvalidMessageError::CompressedFrame);
+    }
+    let data = &payload[FRAME_FLAG_LEN..payload.len() - TAG_LEN];
+
+    match payload[0] {
+        FRAME_RAW => Ok(payload[FRAME_FLAG_LEN..].into()),
+        FRAME_COMPRESSED => {
+            let mut frame = vec![0u8; MAX_FRAME_DATA_LEN + TAG_LEN];
+            let len = match compression {
+                Compression::Lz4 => {
+                    lz4_flex::block::decompress_into(data, &mut frame[..MAX_FRAME_DATA_LEN])
+                        .map_err(|_| error::InvalidMessageError::CompressedFrame)?
+                }
+            };
+            frame.truncate(len + TAG_LEN);
+            Ok(frame.into_boxed_slice())
+        }
+        _ => Err(error::InvalidMessageError::CompressedFrame),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn payload(data: &[u8]) -> Box<[u8]> {
+        [data, &[0u8; TAG_LEN]].concat().into_boxed_slice()
+    }
+
+    #[test]
+    fn test_compress_and_decompress() {
+        let text = b"hello world ".repeat(100);
+        let compressed = compress(Compression::Lz4, &payload(&text));
+        assert_eq!(compressed[0], FRAME_COMPRESSED);
+        assert!(compressed.len() < text.len());
+        assert_eq!(
+            decompress(Compression::Lz4, &compressed).unwrap(),
+            payload(&text)
+        );
+
+        // Incompressible frames are sent as is.
+        let random = (0..=255u8).collect::<Vec<_>>();
+        let raw = compress(Compression::Lz4, &payload(&random));
+        assert_eq!(raw.len(), FRAME_FLAG_LEN + random.len() + TAG_LEN);
+        assert_eq!(raw[0], FRAME_RAW);
+        assert_eq!(
+            decompress(Compression::Lz4, &raw).unwrap(),
+            payload(&random)
+        );
+
+        let mut corrupted = compress(Compression::Lz4, &payload(&text)).into_vec();
+        corrupted[0] = 0x02;
+        assert!(decompress(Compression::Lz4, &corrupted).is_err());
+        assert!(decompress(Compression::Lz4, &payload(&[])).is_err());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(Compression::SUPPORTED), Some(Compression::Lz4));
+        assert_eq!(negotiate(&[]), None);
+    }
+}
@@ -1,10 +1,48 @@
-use serde::{Deserialize, Serialize};
-
 use super::message::SecureMessageType;
+use crate::error;
+
+pub(crate) const SECURE_MSG_HEADER_LEN: usize = 5;
+
+// The largest CBOR-encoded message accepted, bounding the memory buffered for a message
+// until it has been received entirely.
+pub(crate) const MAX_SECURE_MSG_LEN: usize = 1 << 24;
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+// LAYOUT:
+// |0         |1         |2         |3         |4         |
+// |----------|----------|----------|----------|----------|
+// |type      |length                                     |
+// |----------|-------------------------------------------|
+// |CBOR-encoded message                                  |
+// :                                                      :
+// |------------------------------------------------------|
+
+#[derive(Debug, Clone, Copy)]
 #[non_exhaustive]
 pub(crate) struct SecureMessageHeader {
     pub(super) secure_msg_type: SecureMessageType,
     pub(super) length: usize,
 }
+
+impl TryFrom<&[u8; SECURE_MSG_HEADER_LEN]> for SecureMessageHeader {
+    type Error = error::InvalidMessageError;
+
+    fn try_from(value: &[u8; SECURE_MSG_HEADER_LEN]) -> Result<Self, Self::Error> {
+        let length = u32::from_be_bytes([value[1], value[2], value[3], value[4]]) as usize;
+        if length > MAX_SECURE_MSG_LEN {
+            return Err(error::InvalidMessageError::SecureMessageTooLong { length });
+        }
+        Ok(Self {
+            secure_msg_type: SecureMessageType::try_from(value[0])?,
+            length,
+        })
+    }
+}
+
+impl From<SecureMessageHeader> for [u8; SECURE_MSG_HEADER_LEN] {
+    fn from(value: SecureMessageHeader) -> Self {
+        let mut buf = [0u8; SECURE_MSG_HEADER_LEN];
+        buf[0] = value.secure_msg_type.into();
+        buf[1..].copy_from_slice(&u32::to_be_bytes(value.length as u32));
+        buf
+    }
+}
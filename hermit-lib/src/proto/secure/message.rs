@@ -1,12 +1,11 @@
 // Fix for rust-analyzer
 #![allow(non_upper_case_globals)]
 
-use ciborium_io::Write;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use serde::{de, ser, Deserialize, Serialize};
 
 use super::stream::SecureStream;
-use crate::{error, proto::ProtocolVersion};
+use crate::error;
 
 pub(crate) use ring::aead::MAX_TAG_LEN as TAG_LEN;
 
@@ -20,35 +19,59 @@ pub enum SecureMessageType {
     SendResourceResponse = 0x02,
     ReceiveResourceRequest = 0x03,
     ReceiveResourceResponse = 0x04,
-}
+    ReceiverChallenge = 0x05,
+    ReceiverChallengeResponse = 0x06,
+    ClientAuthRequest = 0x07,
+    ClientAuthResponse = 0x08,
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-pub(in crate::proto) struct Header {
-    pub(in crate::proto) message_type: SecureMessageType,
-    pub(in crate::proto) version: ProtocolVersion,
-    pub(in crate::proto) length: usize,
+    ResourceChunk = 0x10,
+    ResourceEnd = 0x11,
+    ResourceAck = 0x12,
 }
 
 // TODO: Separate transport layer protocol (Plain) from application layer protocol (Secure).
-// The former uses plain bytes and a fixed-length header, while the latter uses CBOR and a
-// variable-length header.
+// The former uses plain bytes, while the latter uses CBOR.
+
+// LAYOUT: secure message header || CBOR-encoded message
+// READ: proto/secure/header.rs for the layout of the header.
 
-pub(in crate::proto) trait Secure:
-    Sized + ser::Serialize + de::DeserializeOwned
+#[async_trait::async_trait]
+pub(crate) trait Secure:
+    Sized + Send + Sync + ser::Serialize + de::DeserializeOwned
 {
-    fn send(&self, mut secure_stream: &mut SecureStream) -> Result<(), error::Error> {
-        ciborium::into_writer(self, &mut secure_stream).map_err(|err| match err {
-            ciborium::ser::Error::Io(error) => error,
+    const MESSAGE_TYPE: SecureMessageType;
+
+    // NOTE: The message is encoded in memory first, such that its length is known before
+    // any frame is sent.
+    async fn send(&self, secure_stream: &mut SecureStream) -> Result<(), error::Error> {
+        let mut body = Vec::new();
+        ciborium::into_writer(self, &mut body).map_err(|err| match err {
+            ciborium::ser::Error::Io(error) => error::Error::from(error),
             ciborium::ser::Error::Value(string) => {
                 error::InvalidMessageError::CborSerialization(string).into()
             }
         })?;
-        (&mut secure_stream).flush()
+        secure_stream.send_message(Self::MESSAGE_TYPE, &body).await
     }
-    fn recv(mut secure_stream: &mut SecureStream) -> Result<Self, error::Error> {
-        ciborium::from_reader(&mut secure_stream).map_err(|err| match err {
-            ciborium::de::Error::Io(error) => error,
-            others => error::InvalidMessageError::CborDeserialization(others.to_string()).into(),
-        })
+    async fn recv(secure_stream: &mut SecureStream) -> Result<Self, error::Error> {
+        let secure_msg_type = recv_header(secure_stream).await?;
+        if secure_msg_type != Self::MESSAGE_TYPE {
+            return Err(
+                error::InvalidMessageError::UnexpectedSecureMessageType(secure_msg_type).into(),
+            );
+        }
+        Self::recv_body(secure_stream).await
     }
+    // NOTE: Only use this function after the header has been received with `recv_header`.
+    async fn recv_body(secure_stream: &mut SecureStream) -> Result<Self, error::Error> {
+        let body = secure_stream.recv_body().await?;
+        ciborium::from_reader(body.as_slice())
+            .map_err(|err| error::InvalidMessageError::CborDeserialization(err.to_string()).into())
+    }
+}
+
+pub(crate) async fn recv_header(
+    secure_stream: &mut SecureStream,
+) -> Result<SecureMessageType, error::Error> {
+    secure_stream.recv_header().await
 }
@@ -1,5 +1,7 @@
+pub(crate) mod auth;
 pub(crate) mod buffer;
+pub(crate) mod compression;
 pub(crate) mod header;
 pub(crate) mod message;
 pub(crate) mod stream;
-pub(crate) mod transfer;
+pub mod transfer;
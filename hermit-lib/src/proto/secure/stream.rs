@@ -1,19 +1,19 @@
-use std::cell::RefCell;
+use std::time::Duration;
 
-use async_std::task;
+use async_std::{future, io};
 
-use super::buffer::{ReadBuffer, WriteBuffer};
-use crate::proto::message::Message;
+use super::buffer::ReadBuffer;
+use super::compression::{self, Compression};
+use super::header::{SecureMessageHeader, MAX_SECURE_MSG_LEN, SECURE_MSG_HEADER_LEN};
+use crate::crypto::secrets::{self, KeyUpdateLimits};
+use crate::error;
+use crate::proto::message::{key_update, Message, PlainMessageType, SecureMessageType, TAG_LEN};
 use crate::proto::stream::{Plain, PlainStream};
-use crate::{crypto::secrets, error};
 
 pub trait Secure: Plain {
     type SessionSecrets;
     type PlainType: Plain;
 
-    // fn send(&mut self, secure_msg: impl message::Secure) -> Result<(), error::Error>;
-    // fn recv_header(&mut self) -> Result<message::SecureMessageType, error::Error>;
-    // fn recv<S: message::Secure>(&mut self) -> Result<S, error::Error>;
     fn upgrade(stream: Self::PlainType, secrets: Self::SessionSecrets) -> Self;
     fn downgrade(self) -> Self::PlainType;
 }
@@ -22,7 +22,11 @@ pub struct SecureStream {
     stream: PlainStream,
     session_secrets: secrets::SessionSecrets,
     read_buffer: ReadBuffer,
-    write_buffer: WriteBuffer,
+    // The length of the message body following the header last received.
+    body_len: Option<usize>,
+    compression: Option<Compression>,
+    key_update_limits: KeyUpdateLimits,
+    recv_timeout: Option<Duration>,
 }
 
 impl SecureStream {
@@ -31,11 +35,171 @@ impl SecureStream {
             stream,
             session_secrets,
             read_buffer: ReadBuffer::new(),
-            write_buffer: WriteBuffer::new(),
+            body_len: None,
+            compression: None,
+            key_update_limits: KeyUpdateLimits::default(),
+            recv_timeout: None,
         }
     }
 }
 
+impl SecureStream {
+    pub(crate) fn session_secrets(&self) -> &secrets::SessionSecrets {
+        &self.session_secrets
+    }
+
+    pub(crate) fn len_limit(&self) -> usize {
+        self.stream.len_limit()
+    }
+
+    // Compress the frames of the secure messages sent and received from now on, as
+    // negotiated by both sides.
+    // NOTE: Only use this function in between secure messages, at the same point on both
+    // sides.
+    pub(crate) fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    pub(crate) fn set_key_update_limits(&mut self, limits: KeyUpdateLimits) {
+        self.key_update_limits = limits;
+    }
+
+    // Fail to receive a message that does not arrive within the timeout, if any.
+    pub(crate) fn set_recv_timeout(&mut self, timeout: Option<Duration>) {
+        self.recv_timeout = timeout;
+    }
+
+    // Move on to the next generation of the sealing key, announcing it to the peer, which
+    // moves on to the next generation of its own as well if requested.
+    // NOTE: The frames sealed from now on are only opened by the peer once it has received
+    // the announcement, which precedes them on the stream.
+    pub(crate) async fn update_key(&mut self, update_requested: bool) -> Result<(), error::Error> {
+        let generation = self.session_secrets.update_sealing_key()?;
+        self.stream
+            .send(key_update::KeyUpdate::new(generation, update_requested).into())
+            .await
+    }
+
+    // NOTE: When both sides request an update at once, each answers the other's request, so
+    // both directions still move on by the same number of generations.
+    async fn handle_key_update(&mut self, msg: Message) -> Result<(), error::Error> {
+        let key_update = key_update::KeyUpdate::try_from(msg)?;
+        self.session_secrets
+            .update_opening_key(key_update.generation())?;
+        if key_update.update_requested() {
+            self.update_key(false).await?;
+        }
+        Ok(())
+    }
+
+    // Open a frame received with `recv`, e.g. while waiting for either a plain or a secure
+    // message, such that the secure message it begins can then be received.
+    // NOTE: Only use this function once the previous secure message has been read entirely.
+    pub(crate) fn buffer(&mut self, msg: Message) -> Result<(), error::Error> {
+        let payload = open(&mut self.session_secrets, self.compression, msg)?;
+        self.read_buffer.fill(payload);
+        Ok(())
+    }
+
+    // Send an encoded secure message, split into frames which are sealed separately. The
+    // message always ends at a frame boundary.
+    pub(crate) async fn send_message(
+        &mut self,
+        secure_msg_type: SecureMessageType,
+        body: &[u8],
+    ) -> Result<(), error::Error> {
+        if body.len() > MAX_SECURE_MSG_LEN {
+            return Err(
+                error::InvalidMessageError::SecureMessageTooLong { length: body.len() }.into(),
+            );
+        }
+        let header = <[u8; SECURE_MSG_HEADER_LEN]>::from(SecureMessageHeader {
+            secure_msg_type,
+            length: body.len(),
+        });
+        let data = [header.as_slice(), body].concat();
+
+        // NOTE: Room is left for the tag, and for the flag of a compressed frame.
+        let frame_overhead = match self.compression {
+            Some(_) => TAG_LEN + compression::FRAME_FLAG_LEN,
+            None => TAG_LEN,
+        };
+        for frame in data.chunks(self.len_limit() - frame_overhead) {
+            // NOTE: The key is only updated once there is more to seal, such that the update
+            // never trails the last message, which the peer may not read past.
+            if self
+                .session_secrets
+                .sealing_limit_reached(&self.key_update_limits)
+            {
+                self.update_key(true).await?;
+            }
+            let payload = [frame, &[0u8; TAG_LEN]].concat().into_boxed_slice();
+            let msg = seal(&mut self.session_secrets, self.compression, payload)?;
+            self.stream.send(msg).await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn recv_header(&mut self) -> Result<SecureMessageType, error::Error> {
+        let mut header = [0u8; SECURE_MSG_HEADER_LEN];
+        self.recv_exact(&mut header).await?;
+        let header = SecureMessageHeader::try_from(&header)?;
+        self.body_len = Some(header.length);
+        Ok(header.secure_msg_type)
+    }
+
+    // Receive the encoded body of the message whose header has been received.
+    pub(crate) async fn recv_body(&mut self) -> Result<Vec<u8>, error::Error> {
+        let mut body = vec![0u8; self.body_len.take().unwrap_or(0)];
+        self.recv_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    async fn recv_exact(&mut self, data: &mut [u8]) -> Result<(), error::Error> {
+        let mut filled = 0;
+        while filled < data.len() {
+            if self.read_buffer.is_empty() {
+                let msg = self.recv().await?;
+                let payload = open(&mut self.session_secrets, self.compression, msg)?;
+                self.read_buffer.fill(payload);
+            }
+            filled += self.read_buffer.read(&mut data[filled..]);
+        }
+        Ok(())
+    }
+}
+
+// NOTE: A frame is compressed before it is sealed, such that whole frames are sealed
+// either way.
+fn seal(
+    session_secrets: &mut secrets::SessionSecrets,
+    compression: Option<Compression>,
+    payload: Box<[u8]>,
+) -> Result<Message, error::Error> {
+    let payload = match compression {
+        Some(compression) => compression::compress(compression, &payload),
+        None => payload,
+    };
+    Ok(session_secrets.seal(payload)?)
+}
+
+// NOTE: A plain message (e.g. disconnect) cannot be opened, so it is surfaced as an
+// unexpected message type for the caller to handle.
+fn open(
+    session_secrets: &mut secrets::SessionSecrets,
+    compression: Option<Compression>,
+    msg: Message,
+) -> Result<Box<[u8]>, error::Error> {
+    if msg.plain_msg_type() != PlainMessageType::Secure {
+        return Err(error::InvalidMessageError::UnexpectedMessageType(msg.plain_msg_type()).into());
+    }
+    let payload = session_secrets.open(msg)?;
+    match compression {
+        Some(compression) => Ok(compression::decompress(compression, &payload)?),
+        None => Ok(payload),
+    }
+}
+
 #[async_trait::async_trait]
 impl Plain for SecureStream {
     fn set_len_limit(&mut self, len_limit: usize) {
@@ -46,8 +210,21 @@ impl Plain for SecureStream {
         self.stream.send(msg).await
     }
 
+    // NOTE: Key updates are handled as they are received, such that they may arrive at any
+    // point of the session.
     async fn recv(&mut self) -> Result<Message, error::Error> {
-        self.stream.recv().await
+        loop {
+            let msg = match self.recv_timeout {
+                Some(timeout) => future::timeout(timeout, self.stream.recv())
+                    .await
+                    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??,
+                None => self.stream.recv().await?,
+            };
+            if msg.plain_msg_type() != PlainMessageType::KeyUpdate {
+                return Ok(msg);
+            }
+            self.handle_key_update(msg).await?;
+        }
     }
 }
 
@@ -64,43 +241,105 @@ impl Secure for SecureStream {
     }
 }
 
-// NOTE: TAG_LEN of space has been reserved at the end of the payload when
-// sealing and opening.
+#[cfg(test)]
+mod test {
+    use async_std::net::{TcpListener, TcpStream};
+    use ring::agreement;
 
-impl ciborium_io::Read for &mut &mut SecureStream {
-    type Error = error::Error;
+    use super::*;
+    use crate::crypto::{self, CipherSuite};
+    use crate::proto::stream::BaseStream;
+    use crate::proto::Side;
 
-    fn read_exact(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
-        self.read_buffer.read(data, || {
-            let msg = task::block_on(self.stream.recv())?;
-            let payload = self.session_secrets.open(msg)?;
-            Ok::<_, Self::Error>(payload)
-        })
-    }
-}
+    // A pair of secure streams connected over TCP, sharing the secrets of a session.
+    async fn secure_stream_pair() -> (SecureStream, SecureStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client_stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server_stream, _) = listener.accept().await.unwrap();
 
-impl ciborium_io::Write for &mut &mut SecureStream {
-    type Error = error::Error;
+        let (client_private_key, client_public_key) =
+            crypto::generate_ephemeral_key_pair().unwrap();
+        let (server_private_key, server_public_key) =
+            crypto::generate_ephemeral_key_pair().unwrap();
+        let public_key = |public_key: agreement::PublicKey| {
+            agreement::UnparsedPublicKey::new(
+                &agreement::X25519,
+                public_key.as_ref().try_into().unwrap(),
+            )
+        };
+        let session_secrets = |private_key, public_key, side| {
+            crypto::generate_session_secrets(
+                private_key,
+                public_key,
+                [0u8; 2 * crypto::NONCE_LEN],
+                CipherSuite::Aes128Gcm,
+                [0u8; crypto::TRANSCRIPT_LEN],
+                side,
+            )
+        };
 
-    fn write_all(&mut self, data: &[u8]) -> Result<(), Self::Error> {
-        let stream = RefCell::new(&mut self.stream);
-
-        self.write_buffer.write(
-            data,
-            |payload| {
-                let msg = self.session_secrets.seal(payload)?;
-                task::block_on(stream.borrow_mut().send(msg))?;
-                Ok::<_, Self::Error>(())
-            },
-            || stream.borrow().len_limit(),
+        (
+            SecureStream::new(
+                PlainStream::from(BaseStream::Tcp(client_stream)),
+                session_secrets(
+                    client_private_key,
+                    public_key(server_public_key),
+                    Side::Client,
+                )
+                .await
+                .unwrap(),
+            ),
+            SecureStream::new(
+                PlainStream::from(BaseStream::Tcp(server_stream)),
+                session_secrets(
+                    server_private_key,
+                    public_key(client_public_key),
+                    Side::Server,
+                )
+                .await
+                .unwrap(),
+            ),
         )
     }
 
-    fn flush(&mut self) -> Result<(), Self::Error> {
-        self.write_buffer.flush(|payload| {
-            let msg = self.session_secrets.seal(payload)?;
-            task::block_on(self.stream.send(msg))?;
-            Ok::<_, Self::Error>(())
-        })
+    #[async_std::test]
+    async fn test_key_update() {
+        let (mut sender, mut receiver) = secure_stream_pair().await;
+        sender.set_key_update_limits(KeyUpdateLimits {
+            frames: 1,
+            bytes: u64::MAX,
+        });
+
+        // Every message after the first is sealed under the key of the next generation, and
+        // the receiver moves on to the next generation of its own as requested.
+        for generation in 0..3 {
+            sender
+                .send_message(SecureMessageType::ResourceChunk, b"hello")
+                .await
+                .unwrap();
+            assert_eq!(sender.session_secrets().sealing_generation(), generation);
+
+            assert_eq!(
+                receiver.recv_header().await.unwrap(),
+                SecureMessageType::ResourceChunk
+            );
+            assert_eq!(receiver.recv_body().await.unwrap(), b"hello");
+            assert_eq!(receiver.session_secrets().opening_generation(), generation);
+            assert_eq!(receiver.session_secrets().sealing_generation(), generation);
+        }
+
+        // The updates answered by the receiver precede its next message.
+        receiver
+            .send_message(SecureMessageType::ResourceAck, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            sender.recv_header().await.unwrap(),
+            SecureMessageType::ResourceAck
+        );
+        assert!(sender.recv_body().await.unwrap().is_empty());
+        assert_eq!(sender.session_secrets().opening_generation(), 2);
     }
 }
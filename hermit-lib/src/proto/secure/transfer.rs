@@ -1,33 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_std::io::prelude::*;
+use async_std::io::{Read, Write};
 use ring::signature;
 use serde::{Deserialize, Serialize};
 use serde_with;
 
-use super::message;
+use super::message::{self, Secure, SecureMessageType};
+use super::stream::SecureStream;
+use crate::{crypto, error, secure_msg};
 
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-pub(crate) enum ReceiverControl {
+pub use super::compression::Compression;
+
+// NOTE: chunks are further split into frames according to the message length limit.
+pub(crate) const RESOURCE_CHUNK_LEN: usize = 1 << 14;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum ReceiverControl {
     Password(String),
     PublicKey([u8; signature::ED25519_PUBLIC_KEY_LEN]),
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    File,
+    // Directories carry no data, and are declared such that empty directories are
+    // preserved.
+    Directory,
+}
+
+// MANIFEST:
+// The resources of a request form a manifest, in which each entry has a path relative to
+// the receiver's output directory, made of `/`-separated components. Directories have a
+// size of zero.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    pub size: u64,
+    pub path: String,
+    pub kind: ResourceKind,
+}
+
+pub(crate) const RESOURCE_PATH_SEPARATOR: char = '/';
+
+impl Resource {
+    pub fn file(size: u64, path: impl Into<String>) -> Self {
+        Self {
+            size,
+            path: path.into(),
+            kind: ResourceKind::File,
+        }
+    }
+
+    pub fn directory(path: impl Into<String>) -> Self {
+        Self {
+            size: 0,
+            path: path.into(),
+            kind: ResourceKind::Directory,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == ResourceKind::Directory
+    }
+
+    // The path of the resource under the given directory.
+    // NOTE: The path is only safe to use once the manifest has been validated, such that it
+    // cannot escape the directory.
+    pub fn local_path(&self, dir: &Path) -> PathBuf {
+        self.path
+            .split(RESOURCE_PATH_SEPARATOR)
+            .fold(dir.to_path_buf(), |path, component| path.join(component))
+    }
+
+    fn validate(&self) -> Result<(), error::TransferError> {
+        // NOTE: Separators and prefixes of other platforms are rejected as well.
+        let valid_component = |component: &str| {
+            !matches!(component, "" | "." | "..") && !component.contains(['\\', ':', '\0'])
+        };
+        if !self
+            .path
+            .split(RESOURCE_PATH_SEPARATOR)
+            .all(valid_component)
+            || (self.is_dir() && self.size != 0)
+        {
+            return Err(error::TransferError::InvalidResourcePath(self.path.clone()));
+        }
+        Ok(())
+    }
+}
+
+// Whether the offsets requested by a receiver are within the resources. Directories cannot
+// be resumed, so their offset is always zero.
+pub(crate) fn valid_offsets(resources: &[Resource], offsets: &[u64]) -> bool {
+    offsets.is_empty()
+        || (offsets.len() == resources.len()
+            && resources
+                .iter()
+                .zip(offsets)
+                .all(|(resource, offset)| *offset <= resource.size))
+}
+
+// Validate the paths of a manifest, such that a receiver can write every resource under
+// its output directory without conflicts, and that the total size of the resources can be
+// summed up.
+pub(crate) fn validate_manifest(resources: &[Resource]) -> Result<(), error::TransferError> {
+    resources
+        .iter()
+        .try_fold(0u64, |total, resource| total.checked_add(resource.size))
+        .ok_or(error::TransferError::ManifestSizeOverflow)?;
+
+    let mut kinds = HashMap::with_capacity(resources.len());
+    for resource in resources {
+        resource.validate()?;
+        if kinds
+            .insert(resource.path.as_str(), resource.kind)
+            .is_some()
+        {
+            return Err(error::TransferError::DuplicateResourcePath(
+                resource.path.clone(),
+            ));
+        }
+    }
+    // NOTE: Only directories may contain other resources, while their parents need not be
+    // declared.
+    for resource in resources {
+        let nested_under_file = resource
+            .path
+            .match_indices(RESOURCE_PATH_SEPARATOR)
+            .any(|(index, _)| kinds.get(&resource.path[..index]) == Some(&ResourceKind::File));
+        if nested_under_file {
+            return Err(error::TransferError::NestedResourcePath(
+                resource.path.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[serde_with::serde_as]
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-pub(crate) struct SendResourceRequest {
-    // The size and name of the resource to be sent.
-    pub resources: Vec<(u64, String)>,
+pub struct SendResourceRequest {
+    // The resources to be sent.
+    pub resources: Vec<Resource>,
     // Suggest an expiry duration to the server which may accept or reject it.
     #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
     pub expiry_duration: Option<chrono::Duration>,
     // The control method to be used by the receiver to authenticate (if any).
     pub receiver_control: Option<ReceiverControl>,
+    // The compressions supported by the sender in order of preference, or empty to send the
+    // resources uncompressed.
+    pub compression: Vec<Compression>,
 }
 
-impl message::Secure for SendResourceRequest {}
+secure_msg!(SendResourceRequest, SecureMessageType::SendResourceRequest);
 
 // NOTE: the resource ID length is dynamic, depending on the number of active resources
 // on the server, and also the duration till the expiry time.
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-pub struct ResourceId(Vec<u8>);
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceId(pub(crate) Vec<u8>);
+
+// Each niceware word encodes 2 bytes of the resource ID.
+pub(crate) const RESOURCE_ID_WORD_LEN: usize = 2;
+pub(crate) const RESOURCE_ID_SEPARATOR: char = '-';
+
+impl ResourceId {
+    pub(crate) fn generate(word_count: usize) -> Result<Self, error::CryptoError> {
+        let mut id = vec![0u8; word_count * RESOURCE_ID_WORD_LEN];
+        crypto::fill_random(&mut id)?;
+        Ok(Self(id))
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.0.len() / RESOURCE_ID_WORD_LEN
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), error::ResourceIdError> {
+        if self.0.is_empty() || !self.0.len().is_multiple_of(RESOURCE_ID_WORD_LEN) {
+            return Err(error::ResourceIdError::InvalidLength(self.0.len()));
+        }
+        Ok(())
+    }
+}
+
+// The resource ID is rendered as niceware words, e.g. `tacking-hamlet-pentagon`.
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = niceware::bytes_to_passphrase(&self.0).map_err(|_| fmt::Error)?;
+        write!(f, "{}", words.join(&RESOURCE_ID_SEPARATOR.to_string()))
+    }
+}
+
+impl FromStr for ResourceId {
+    type Err = error::ResourceIdError;
+
+    // NOTE: Words may be separated by the separator or whitespaces, and are case-insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s
+            .split(|c: char| c == RESOURCE_ID_SEPARATOR || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            return Err(error::ResourceIdError::Empty);
+        }
+
+        niceware::passphrase_to_bytes(&words)
+            .map(Self)
+            .map_err(|err| match err {
+                niceware::Error::UnknownWord { word } => error::ResourceIdError::UnknownWord(word),
+                // NOTE: Decoding only fails on unknown words.
+                others => error::ResourceIdError::UnknownWord(others.to_string()),
+            })
+    }
+}
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub(crate) enum SendResourceResponse {
@@ -36,29 +223,51 @@ pub(crate) enum SendResourceResponse {
         id: ResourceId,
         // The actual expiry time of the resource.
         expiry: chrono::DateTime<chrono::Utc>,
+        // The compression picked by the server, if any.
+        compression: Option<Compression>,
     },
     InvalidReceiverControl,
     InvalidExpiry,
     ResourceTooLarge,
+    InvalidManifest,
+    // The server only accepts resources from authenticated clients.
+    Unauthorized,
 }
 
-impl message::Secure for SendResourceResponse {}
+secure_msg!(
+    SendResourceResponse,
+    SecureMessageType::SendResourceResponse
+);
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
-pub(crate) struct ReceiveResourceRequest {
+pub struct ReceiveResourceRequest {
     pub id: ResourceId,
     pub control: Option<ReceiverControl>,
+    // The byte offset from which to receive each resource in the declared order, such that
+    // a partial download can be resumed. Empty to receive all resources from the start.
+    pub offsets: Vec<u64>,
+    // The compressions supported by the receiver in order of preference, or empty to
+    // receive the resources uncompressed.
+    pub compression: Vec<Compression>,
 }
 
-impl message::Secure for ReceiveResourceRequest {}
+secure_msg!(
+    ReceiveResourceRequest,
+    SecureMessageType::ReceiveResourceRequest
+);
 
+#[serde_with::serde_as]
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub(crate) enum ReceiveResourceResponse {
     Ok {
-        // The sizes and names of the files or directories (or a combination of both) to be received.
-        size: Vec<u64>,
-        name: Vec<String>,
+        // The files or directories (or a combination of both) to be received.
+        resources: Vec<Resource>,
+        // The digest of each file as declared by its sender, and none for directories.
+        #[serde_as(as = "Vec<Option<serde_with::Bytes>>")]
+        digests: Vec<Option<[u8; crypto::RESOURCE_DIGEST_LEN]>>,
         expiry: chrono::DateTime<chrono::Utc>,
+        // The compression picked by the server, if any.
+        compression: Option<Compression>,
     },
     // The reason for the failure is not specified deliberately.
     // Some possible reasons include:
@@ -69,9 +278,195 @@ pub(crate) enum ReceiveResourceResponse {
     // 5. The receiver control is invalid.
     // 6. The receiver control is not provided.
     Failed,
+    // The offsets do not match the resources, which is only revealed to a verified
+    // receiver.
+    InvalidOffsets,
 }
 
-impl message::Secure for ReceiveResourceResponse {}
+secure_msg!(
+    ReceiveResourceResponse,
+    SecureMessageType::ReceiveResourceResponse
+);
+
+// RECEIVER CHALLENGE:
+// If the resource is locked with `ReceiverControl::PublicKey` and the receiver presents the
+// same public key, the server sends a `ReceiverChallenge` before `ReceiveResourceResponse`.
+// The receiver proves possession of the private key with a `ReceiverChallengeResponse`,
+// signing over the challenge, the resource ID and a value bound to the session.
+
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ReceiverChallenge {
+    #[serde_as(as = "serde_with::Bytes")]
+    pub challenge: [u8; crypto::RECEIVER_CHALLENGE_LEN],
+}
+
+secure_msg!(ReceiverChallenge, SecureMessageType::ReceiverChallenge);
+
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ReceiverChallengeResponse {
+    #[serde_as(as = "serde_with::Bytes")]
+    pub signature: [u8; crypto::ED25519_SIGNATURE_LEN],
+}
+
+secure_msg!(
+    ReceiverChallengeResponse,
+    SecureMessageType::ReceiverChallengeResponse
+);
+
+// DATA PHASE:
+// After `SendResourceResponse::Ok` (sender is the client) or `ReceiveResourceResponse::Ok`
+// (sender is the server), the sender sends each file in the declared order as zero or more
+// `ResourceChunk` followed by a `ResourceEnd`. The total length of the chunks MUST equal
+// the declared size of the file, less the offset requested by the receiver if any.
+// Directories are skipped. The `ResourceEnd` carries the SHA-256 digest of the whole file,
+// which the receiver verifies. Once all files have been received, the receiver sends a
+// `ResourceAck`. If a compression has been negotiated, it applies from the first file up
+// to and including the `ResourceAck`.
+
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ResourceChunk {
+    #[serde_as(as = "serde_with::Bytes")]
+    pub data: Vec<u8>,
+}
+
+secure_msg!(ResourceChunk, SecureMessageType::ResourceChunk);
+
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ResourceEnd {
+    #[serde_as(as = "serde_with::Bytes")]
+    pub digest: [u8; crypto::RESOURCE_DIGEST_LEN],
+}
+
+secure_msg!(ResourceEnd, SecureMessageType::ResourceEnd);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct ResourceAck;
+
+secure_msg!(ResourceAck, SecureMessageType::ResourceAck);
+
+// NOTE: The progress is called with the length of each chunk once it has been sent or
+// received, and with zero before the first chunk.
+
+// Send a file, ending with the given digest of the whole file, or otherwise the digest of
+// the data read, as only the remainder of a file may be sent.
+pub(crate) async fn send_resource(
+    secure_stream: &mut SecureStream,
+    size: u64,
+    mut reader: impl Read + Unpin,
+    digest: Option<[u8; crypto::RESOURCE_DIGEST_LEN]>,
+    mut progress: impl FnMut(u64),
+) -> Result<(), error::Error> {
+    let mut buffer = vec![0u8; RESOURCE_CHUNK_LEN];
+    let mut hasher = crypto::ResourceHasher::new();
+    let mut sent = 0;
+    progress(0);
+
+    while sent < size {
+        let len = (size - sent).min(RESOURCE_CHUNK_LEN as u64) as usize;
+        let read_len = reader.read(&mut buffer[..len]).await?;
+        if read_len == 0 {
+            return Err(error::TransferError::ResourceSizeMismatch {
+                declared: size,
+                transferred: sent,
+            }
+            .into());
+        }
+
+        hasher.update(&buffer[..read_len]);
+        ResourceChunk {
+            data: buffer[..read_len].to_vec(),
+        }
+        .send(secure_stream)
+        .await?;
+        sent += read_len as u64;
+        progress(read_len as u64);
+    }
+
+    ResourceEnd {
+        digest: digest.unwrap_or_else(|| hasher.finish()),
+    }
+    .send(secure_stream)
+    .await
+}
+
+// Receive a file, verifying the digest of the whole file against the one sent. The hasher
+// has already been updated with any part of the file received before.
+pub(crate) async fn recv_resource(
+    secure_stream: &mut SecureStream,
+    size: u64,
+    mut writer: impl Write + Unpin,
+    mut hasher: crypto::ResourceHasher,
+    mut progress: impl FnMut(u64),
+) -> Result<[u8; crypto::RESOURCE_DIGEST_LEN], error::Error> {
+    let mut received = 0;
+    progress(0);
+
+    loop {
+        match message::recv_header(secure_stream).await? {
+            SecureMessageType::ResourceChunk => {
+                let chunk = ResourceChunk::recv_body(secure_stream).await?;
+                received += chunk.data.len() as u64;
+                if received > size {
+                    return Err(error::TransferError::ResourceSizeMismatch {
+                        declared: size,
+                        transferred: received,
+                    }
+                    .into());
+                }
+                hasher.update(&chunk.data);
+                writer.write_all(&chunk.data).await?;
+                progress(chunk.data.len() as u64);
+            }
+            SecureMessageType::ResourceEnd => {
+                let ResourceEnd { digest } = ResourceEnd::recv_body(secure_stream).await?;
+                if received != size {
+                    return Err(error::TransferError::ResourceSizeMismatch {
+                        declared: size,
+                        transferred: received,
+                    }
+                    .into());
+                }
+                writer.flush().await?;
+                if hasher.finish() != digest {
+                    return Err(error::TransferError::DigestMismatch.into());
+                }
+                return Ok(digest);
+            }
+            others => {
+                return Err(error::InvalidMessageError::UnexpectedSecureMessageType(others).into())
+            }
+        }
+    }
+}
+
+// Update the hasher with the part of a file received before, which must be exactly as long
+// as the offset it is resumed from.
+pub(crate) async fn hash_partial(
+    hasher: &mut crypto::ResourceHasher,
+    mut partial: impl Read + Unpin,
+    len: u64,
+) -> Result<(), error::Error> {
+    let mut buffer = vec![0u8; RESOURCE_CHUNK_LEN];
+    let mut read = 0;
+    while read < len {
+        let chunk_len = (len - read).min(RESOURCE_CHUNK_LEN as u64) as usize;
+        let read_len = partial.read(&mut buffer[..chunk_len]).await?;
+        if read_len == 0 {
+            return Err(error::TransferError::ResourceSizeMismatch {
+                declared: len,
+                transferred: read,
+            }
+            .into());
+        }
+        hasher.update(&buffer[..read_len]);
+        read += read_len as u64;
+    }
+    Ok(())
+}
 
 #[cfg(test)]
 mod test {
@@ -80,14 +475,16 @@ mod test {
     #[test]
     fn test_send_resource_request_serialization() {
         let request = SendResourceRequest {
-            resources: vec![(100_000_000, "ABCDEFGHIJKLMNOPQRSTUVWXYZ.txt".to_owned()); 10],
+            resources: vec![Resource::file(100_000_000, "ABCDEFGHIJKLMNOPQRSTUVWXYZ.txt"); 10],
             expiry_duration: Some(chrono::Duration::days(1)),
             receiver_control: Some(ReceiverControl::Password("test".to_owned())),
+            compression: Vec::new(),
         };
         let request_copy = SendResourceRequest {
-            resources: vec![(100_000_000, "ABCDEFGHIJKLMNOPQRSTUVWXYZ.txt".to_owned()); 10],
+            resources: vec![Resource::file(100_000_000, "ABCDEFGHIJKLMNOPQRSTUVWXYZ.txt"); 10],
             expiry_duration: Some(chrono::Duration::days(1)),
             receiver_control: Some(ReceiverControl::Password("test".to_owned())),
+            compression: Vec::new(),
         };
         let mut msg = Vec::new();
         ciborium::into_writer(&request, &mut msg).unwrap();
@@ -96,4 +493,97 @@ mod test {
         let deserialized = ciborium::from_reader::<SendResourceRequest, _>(msg.as_slice()).unwrap();
         assert_eq!(request_copy, deserialized);
     }
+
+    #[test]
+    fn test_validate_manifest() {
+        assert!(validate_manifest(&[
+            Resource::directory("dir"),
+            Resource::directory("dir/empty"),
+            Resource::file(5, "dir/a.txt"),
+            Resource::file(0, "b.txt"),
+        ])
+        .is_ok());
+
+        for path in [
+            "",
+            ".",
+            "..",
+            "../a.txt",
+            "dir/../../a.txt",
+            "/etc/passwd",
+            "dir/",
+        ] {
+            assert!(matches!(
+                validate_manifest(&[Resource::file(0, path)]),
+                Err(error::TransferError::InvalidResourcePath(_))
+            ));
+        }
+        assert!(validate_manifest(&[Resource::file(0, "C:\\Windows")]).is_err());
+        assert!(validate_manifest(&[Resource {
+            size: 1,
+            ..Resource::directory("dir")
+        }])
+        .is_err());
+        assert!(matches!(
+            validate_manifest(&[Resource::directory("a"), Resource::file(0, "a")]),
+            Err(error::TransferError::DuplicateResourcePath(path)) if path == "a"
+        ));
+        assert!(matches!(
+            validate_manifest(&[Resource::file(0, "a/b"), Resource::file(0, "a")]),
+            Err(error::TransferError::NestedResourcePath(path)) if path == "a/b"
+        ));
+        assert!(matches!(
+            validate_manifest(&[Resource::file(0, "a"), Resource::directory("a/b/c")]),
+            Err(error::TransferError::NestedResourcePath(path)) if path == "a/b/c"
+        ));
+        assert!(validate_manifest(&[Resource::file(0, "a"), Resource::file(0, "ab/c")]).is_ok());
+        assert!(matches!(
+            validate_manifest(&[Resource::file(u64::MAX, "a"), Resource::file(1, "b")]),
+            Err(error::TransferError::ManifestSizeOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_valid_offsets() {
+        let resources = [Resource::directory("dir"), Resource::file(5, "dir/a.txt")];
+        assert!(valid_offsets(&resources, &[]));
+        assert!(valid_offsets(&resources, &[0, 0]));
+        assert!(valid_offsets(&resources, &[0, 5]));
+        assert!(!valid_offsets(&resources, &[0, 6]));
+        assert!(!valid_offsets(&resources, &[1, 0]));
+        assert!(!valid_offsets(&resources, &[0]));
+    }
+
+    #[test]
+    fn test_resource_local_path() {
+        let dir = Path::new("out");
+        assert_eq!(
+            Resource::file(0, "a.txt").local_path(dir),
+            dir.join("a.txt")
+        );
+        assert_eq!(
+            Resource::file(0, "dir/sub/a.txt").local_path(dir),
+            dir.join("dir").join("sub").join("a.txt")
+        );
+    }
+
+    #[test]
+    fn test_resource_id_words() {
+        let id = ResourceId::generate(3).unwrap();
+        assert_eq!(id.word_count(), 3);
+        assert_eq!(id.to_string().parse::<ResourceId>().unwrap(), id);
+
+        let id = ResourceId(vec![0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(id.to_string(), "a-zyzzyva");
+        assert_eq!(" A  Zyzzyva ".parse::<ResourceId>().unwrap(), id);
+
+        assert!(matches!(
+            "".parse::<ResourceId>(),
+            Err(error::ResourceIdError::Empty)
+        ));
+        assert!(matches!(
+            "a-notaword".parse::<ResourceId>(),
+            Err(error::ResourceIdError::UnknownWord(word)) if word == "notaword"
+        ));
+    }
 }
@@ -1,8 +1,13 @@
+pub(crate) mod quic;
+
+use std::net::SocketAddr;
 use std::pin::Pin;
 
 pub use async_std::net::TcpStream;
 use quinn::{RecvStream, SendStream};
 
+use crate::error;
+
 pub(crate) use crate::proto::plain::stream::{Plain, PlainStream};
 pub(crate) use crate::proto::secure::stream::{Secure, SecureStream};
 
@@ -50,6 +55,30 @@ pub enum BaseStream {
     Quic(QuicStream),
 }
 
+impl BaseStream {
+    pub async fn connect_tcp(addr: SocketAddr) -> Result<Self, error::Error> {
+        Ok(BaseStream::Tcp(TcpStream::connect(addr).await?))
+    }
+
+    // NOTE: A single bidirectional stream is opened on a new QUIC connection.
+    pub async fn connect_quic(addr: SocketAddr) -> Result<Self, error::Error> {
+        let local_addr: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let mut endpoint = quinn::Endpoint::client(local_addr)?;
+        endpoint.set_default_client_config(quic::client_config());
+
+        let connection = endpoint.connect(addr, quic::SERVER_NAME)?.await?;
+        let (send_stream, recv_stream) = connection.open_bi().await?;
+
+        Ok(BaseStream::Quic(QuicStream {
+            send_stream,
+            recv_stream,
+        }))
+    }
+}
+
 impl futures_io::AsyncRead for BaseStream {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -100,8 +129,9 @@ impl futures_io::AsyncWrite for BaseStream {
 mod test {
     // TODO: Fix this mess by using a proto::prelude module.
     use crate::{
-        crypto::{self, NONCE_LEN, SIGNED_CONTENT_LEN},
+        crypto,
         proto::{
+            encode_versions,
             plain::{
                 handshake::{ClientHelloMessage, ServerHelloMessage},
                 stream::{Plain, PlainStream},
@@ -109,9 +139,9 @@ mod test {
             secure::{
                 message::Secure,
                 stream::SecureStream,
-                transfer::{ReceiverControl, SendResourceRequest},
+                transfer::{ReceiverControl, Resource, SendResourceRequest},
             },
-            Side,
+            Side, CURRENT_PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
         },
     };
 
@@ -142,23 +172,23 @@ mod test {
             let nonce = crypto::generate_nonce().await.unwrap();
 
             let received_msg = ClientHelloMessage::try_from(stream.recv().await.unwrap()).unwrap();
-            let mut message = [0u8; SIGNED_CONTENT_LEN];
-            message[..NONCE_LEN].copy_from_slice(&received_msg.nonce);
-            message[NONCE_LEN..2 * NONCE_LEN].copy_from_slice(&nonce);
-            message[2 * NONCE_LEN..].copy_from_slice(public_key.as_ref());
-
-            let msg = ServerHelloMessage {
+            let (msg, nonces) = crypto::sign_server_hello(
+                &received_msg,
                 nonce,
-                public_key_bytes: public_key.as_ref().try_into().unwrap(),
-                signature: sig_key_pair.sign(&message).as_ref().try_into().unwrap(),
-            };
+                public_key.as_ref().try_into().unwrap(),
+                CURRENT_PROTOCOL_VERSION,
+                crypto::CipherSuite::Aes128Gcm,
+                &sig_key_pair,
+            );
 
             stream.send(msg.into()).await.unwrap();
 
             let secrets = crypto::generate_session_secrets(
                 private_key,
                 UnparsedPublicKey::new(&agreement::X25519, received_msg.public_key_bytes),
-                message[..2 * NONCE_LEN].try_into().unwrap(),
+                nonces,
+                crypto::CipherSuite::Aes128Gcm,
+                crypto::handshake_transcript(&received_msg, &msg),
                 Side::Server,
             )
             .await
@@ -166,7 +196,7 @@ mod test {
 
             let mut stream = SecureStream::new(stream, secrets);
 
-            let msg = SendResourceRequest::recv(&mut stream).unwrap();
+            let msg = SendResourceRequest::recv(&mut stream).await.unwrap();
             println!("Received msg: {:?}", msg);
         });
 
@@ -183,6 +213,8 @@ mod test {
             nonce,
             public_key_bytes: <[u8; crypto::X25519_PUBLIC_KEY_LEN]>::try_from(public_key.as_ref())
                 .unwrap(),
+            versions: encode_versions(SUPPORTED_PROTOCOL_VERSIONS),
+            suites: crypto::suite::encode(crypto::CipherSuite::SUPPORTED),
         };
 
         stream.send(msg.into()).await.unwrap();
@@ -190,21 +222,29 @@ mod test {
         let received_msg = ServerHelloMessage::try_from(stream.recv().await.unwrap()).unwrap();
 
         let (pub_key, nonces) =
-            crypto::verify_server_hello(received_msg, nonce, &sig_pub_key).unwrap();
-
-        let secrets = crypto::generate_session_secrets(private_key, pub_key, nonces, Side::Client)
-            .await
-            .unwrap();
+            crypto::verify_server_hello(received_msg, &msg, &sig_pub_key).unwrap();
+
+        let secrets = crypto::generate_session_secrets(
+            private_key,
+            pub_key,
+            nonces,
+            crypto::CipherSuite::Aes128Gcm,
+            crypto::handshake_transcript(&msg, &received_msg),
+            Side::Client,
+        )
+        .await
+        .unwrap();
 
         let mut secure = SecureStream::new(stream, secrets);
 
         let secure_msg = SendResourceRequest {
-            resources: vec![(0, "test".to_string()); 1000],
+            resources: vec![Resource::file(0, "test"); 1000],
             expiry_duration: Some(Duration::days(3)),
             receiver_control: Some(ReceiverControl::Password("password".to_string())),
+            compression: Vec::new(),
         };
 
-        secure_msg.send(&mut secure).unwrap();
+        secure_msg.send(&mut secure).await.unwrap();
         println!("Finished transmitting!");
 
         join_handle.await;
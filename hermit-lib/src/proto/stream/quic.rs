@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::error;
+
+pub(crate) const ALPN_PROTOCOL: &[u8] = b"hermit";
+pub(crate) const SERVER_NAME: &str = "hermit";
+
+// NOTE: The TLS layer of QUIC is only used as a transport, and the server is authenticated
+// through the signed server hello of the Hermit handshake instead. As such, the server
+// generates an ephemeral self-signed certificate which the client does not verify.
+
+pub(crate) fn server_config() -> Result<quinn::ServerConfig, error::Error> {
+    let cert = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_owned()])
+        .map_err(|err| rustls::Error::General(err.to_string()))?;
+    let cert_der = cert
+        .serialize_der()
+        .map_err(|err| rustls::Error::General(err.to_string()))?;
+    let key_der = cert.serialize_private_key_der();
+
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(cert_der)],
+            rustls::PrivateKey(key_der),
+        )?;
+    crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+pub(crate) fn client_config() -> quinn::ClientConfig {
+    let mut crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(SkipServerCertVerification))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+    quinn::ClientConfig::new(Arc::new(crypto))
+}
+
+struct SkipServerCertVerification;
+
+impl rustls::client::ServerCertVerifier for SkipServerCertVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
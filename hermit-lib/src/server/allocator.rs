@@ -0,0 +1,112 @@
+use super::config::Config;
+use super::storage::{ResourceMetadata, Storage};
+use crate::error::{CryptoError, Error, StorageError};
+use crate::proto::message::transfer::{ResourceId, RESOURCE_ID_WORD_LEN};
+
+const MIN_WORD_COUNT: usize = 1;
+// NOTE: 8 words (128 bits) is more than enough for any realistic configuration.
+const MAX_WORD_COUNT: usize = 8;
+
+// The number of words needed such that the probability of any guess hitting an active
+// resource stays under the configured bound.
+//
+// With `n` active resources (including the new one), and an attacker making `rate` guesses
+// per second for `duration` seconds until the resource expires, the probability of a
+// successful guess is at most `n * rate * duration / 2^bits`. A collision with an existing
+// resource is less likely than that, and is handled by allocating again regardless.
+pub(super) fn word_count(
+    active: usize,
+    expiry_duration: chrono::Duration,
+    config: &Config,
+) -> usize {
+    let guesses = (config.id_guess_rate * expiry_duration.num_seconds() as f64).max(1.0);
+    let bits = ((active as f64 + 1.0) * guesses / config.id_max_guess_probability).log2();
+    let word_bits = (RESOURCE_ID_WORD_LEN * 8) as f64;
+
+    ((bits / word_bits).ceil() as usize).clamp(MIN_WORD_COUNT, MAX_WORD_COUNT)
+}
+
+// Allocate a new resource ID and insert the metadata of the resource under it in one step,
+// such that two sessions cannot end up with the same ID.
+pub(super) async fn allocate(
+    storage: &Storage,
+    expiry_duration: chrono::Duration,
+    config: &Config,
+    metadata: impl Fn(ResourceId) -> ResourceMetadata,
+) -> Result<ResourceId, Error> {
+    let word_count = word_count(storage.len().await, expiry_duration, config);
+    insert_new(storage, || ResourceId::generate(word_count), metadata).await
+}
+
+// NOTE: The insertion fails if the ID is already taken, including by another session since it
+// was generated, in which case another one is generated.
+async fn insert_new(
+    storage: &Storage,
+    mut generate: impl FnMut() -> Result<ResourceId, CryptoError>,
+    metadata: impl Fn(ResourceId) -> ResourceMetadata,
+) -> Result<ResourceId, Error> {
+    loop {
+        let id = generate()?;
+        match storage.insert(metadata(id.clone())).await {
+            Ok(()) => return Ok(id),
+            Err(Error::Storage(StorageError::DuplicateResource)) => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proto::message::transfer::Resource;
+    use crate::server::DownloadState;
+
+    #[async_std::test]
+    async fn test_insert_new_colliding_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::open(dir.path()).await.unwrap();
+        let metadata = |id: ResourceId| ResourceMetadata {
+            id,
+            resources: vec![Resource::file(5, "a.txt")],
+            digests: Vec::new(),
+            receiver_control: None,
+            expiry: chrono::Utc::now() + chrono::Duration::hours(1),
+            state: DownloadState::Uploading,
+        };
+        let taken = ResourceId(vec![1, 2]);
+        storage.insert(metadata(taken.clone())).await.unwrap();
+
+        // The taken ID is generated first, as if another session had just inserted it.
+        let mut ids = vec![ResourceId(vec![3, 4]), taken.clone()];
+        let id = insert_new(&storage, || Ok(ids.pop().unwrap()), metadata)
+            .await
+            .unwrap();
+        assert_eq!(id, ResourceId(vec![3, 4]));
+        assert!(ids.is_empty());
+        assert!(storage.get(&id).await.is_some());
+        assert!(storage.get(&taken).await.is_some());
+    }
+
+    #[test]
+    fn test_word_count() {
+        let config = Config::new("");
+        let hour = chrono::Duration::hours(1);
+
+        let word_count = word_count(0, hour, &config);
+        assert!(word_count >= MIN_WORD_COUNT);
+        assert!(word_count <= super::word_count(1_000_000, hour, &config));
+        assert!(word_count <= super::word_count(0, chrono::Duration::days(30), &config));
+
+        let bound = |words: usize, active: usize, duration: chrono::Duration| {
+            (active + 1) as f64 * config.id_guess_rate * duration.num_seconds() as f64
+                / 2f64.powi((words * RESOURCE_ID_WORD_LEN * 8) as i32)
+        };
+        assert!(bound(word_count, 0, hour) <= config.id_max_guess_probability);
+        assert!(bound(word_count - 1, 0, hour) > config.id_max_guess_probability);
+
+        assert_eq!(
+            super::word_count(usize::MAX, chrono::Duration::weeks(1000), &config),
+            MAX_WORD_COUNT
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::crypto::secrets::KeyUpdateLimits;
+use crate::crypto::{CipherSuite, ED25519_PUBLIC_KEY_LEN};
+use crate::proto::message::MAX_LEN_LIMIT;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // The directory in which uploaded resources and their metadata are stored.
+    pub storage_dir: PathBuf,
+    // The maximum total size of the resources in a single send request.
+    pub max_resource_size: u64,
+    // The expiry duration used when the client does not suggest one.
+    pub expiry_duration: chrono::Duration,
+    // The range which a suggested expiry duration is clamped to.
+    pub min_expiry_duration: chrono::Duration,
+    pub max_expiry_duration: chrono::Duration,
+    // The number of resource ID guesses per second an attacker is assumed to make.
+    pub id_guess_rate: f64,
+    // The upper bound on the probability of any guess hitting an active resource.
+    pub id_max_guess_probability: f64,
+    // The largest message length limit accepted from an `AdjustLenLimitRequest`. Requests
+    // not above the current limit are always accepted.
+    pub max_len_limit: usize,
+    // The cipher suites which a client may pick from.
+    pub cipher_suites: Vec<CipherSuite>,
+    // The amount of data sealed under a key after which it is updated.
+    pub key_update_limits: KeyUpdateLimits,
+    // The time a sender may take to send each chunk of an upload, after which the upload is
+    // discarded.
+    pub upload_chunk_timeout: Duration,
+    // The signature public keys of the clients allowed to authenticate. If set, only
+    // authenticated clients may send resources; otherwise any client may, and any key is
+    // accepted for authentication.
+    pub allowed_clients: Option<Vec<[u8; ED25519_PUBLIC_KEY_LEN]>>,
+}
+
+impl Config {
+    pub fn new<P: Into<PathBuf>>(storage_dir: P) -> Self {
+        Self {
+            storage_dir: storage_dir.into(),
+            max_resource_size: 1 << 30,
+            expiry_duration: chrono::Duration::days(1),
+            min_expiry_duration: chrono::Duration::minutes(1),
+            max_expiry_duration: chrono::Duration::weeks(1),
+            id_guess_rate: 1000.0,
+            id_max_guess_probability: 1e-6,
+            max_len_limit: MAX_LEN_LIMIT,
+            cipher_suites: CipherSuite::SUPPORTED.to_vec(),
+            key_update_limits: KeyUpdateLimits::default(),
+            upload_chunk_timeout: Duration::from_secs(60),
+            allowed_clients: None,
+        }
+    }
+}
@@ -0,0 +1,733 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::net::TcpListener;
+use async_std::task;
+use ring::signature::Ed25519KeyPair;
+
+use super::config::Config;
+use super::reaper;
+use super::session::{self, Context};
+use super::storage::Storage;
+use crate::error::Error;
+use crate::proto::stream::{quic, BaseStream, QuicStream};
+
+// NOTE: Failing to accept a connection, e.g. when out of file descriptors, usually persists
+// for a while, so retries are delayed increasingly up to the maximum.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+pub struct Listener {
+    tcp_listener: TcpListener,
+    quic_endpoint: quinn::Endpoint,
+}
+
+impl Listener {
+    pub async fn bind(tcp_addr: SocketAddr, quic_addr: SocketAddr) -> Result<Self, Error> {
+        let tcp_listener = TcpListener::bind(tcp_addr).await?;
+        let quic_endpoint = quinn::Endpoint::server(quic::server_config()?, quic_addr)?;
+
+        Ok(Self {
+            tcp_listener,
+            quic_endpoint,
+        })
+    }
+
+    pub fn tcp_local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.tcp_listener.local_addr()?)
+    }
+
+    pub fn quic_local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.quic_endpoint.local_addr()?)
+    }
+
+    // NOTE: Both transports are served concurrently, and each accepted connection runs its
+    // session on a separate task. Errors within a session are only logged. Expired resources
+    // are reaped in the background.
+    pub async fn listen(self, sig_key_pair: Ed25519KeyPair, config: Config) -> Result<(), Error> {
+        let storage = Storage::open(&config.storage_dir).await?;
+        let context = Arc::new(Context {
+            sig_key_pair,
+            storage,
+            config,
+        });
+
+        futures::try_join!(
+            Self::listen_tcp(self.tcp_listener, context.clone()),
+            Self::listen_quic(self.quic_endpoint, context.clone()),
+            reaper::reap(context),
+        )?;
+
+        Ok(())
+    }
+
+    async fn listen_tcp(tcp_listener: TcpListener, context: Arc<Context>) -> Result<(), Error> {
+        let mut backoff = MIN_ACCEPT_BACKOFF;
+        loop {
+            let (stream, peer_addr) = match tcp_listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    tracing::warn!("Failed to accept TCP connection: {}", error);
+                    task::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                    continue;
+                }
+            };
+            backoff = MIN_ACCEPT_BACKOFF;
+            let context = context.clone();
+
+            task::spawn(async move {
+                if let Err(error) = session::serve(BaseStream::Tcp(stream), context).await {
+                    tracing::warn!("TCP session with {} failed: {}", peer_addr, error);
+                }
+            });
+        }
+    }
+
+    async fn listen_quic(
+        quic_endpoint: quinn::Endpoint,
+        context: Arc<Context>,
+    ) -> Result<(), Error> {
+        while let Some(connecting) = quic_endpoint.accept().await {
+            let peer_addr = connecting.remote_address();
+            let context = context.clone();
+
+            task::spawn(async move {
+                let session_result = async {
+                    let connection = connecting.await?;
+                    let (send_stream, recv_stream) = connection.accept_bi().await?;
+                    let stream = BaseStream::Quic(QuicStream {
+                        send_stream,
+                        recv_stream,
+                    });
+                    session::serve(stream, context).await
+                }
+                .await;
+
+                if let Err(error) = session_result {
+                    tracing::warn!("QUIC session with {} failed: {}", peer_addr, error);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::net::TcpStream;
+    use ring::signature::KeyPair;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::client::{Client, ServerSigPubKey, UpgradedConnection};
+    use crate::crypto;
+    use crate::crypto::secrets::KeyUpdateLimits;
+    use crate::error::{ClientAuthError, CryptoError, ResourceRequestError};
+    use crate::proto::message::{transfer, MAX_LEN_LIMIT};
+
+    // Spawn a server on localhost with the default configuration adjusted by `configure`,
+    // returning its TCP address, its signature public key and its storage directory, which
+    // must be kept until the end of the test.
+    async fn spawn_server(configure: impl FnOnce(&mut Config)) -> (SocketAddr, Vec<u8>, TempDir) {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = sig_key_pair.public_key().as_ref().to_owned();
+
+        let localhost = SocketAddr::from(([127, 0, 0, 1], 0));
+        let listener = Listener::bind(localhost, localhost).await.unwrap();
+        let tcp_addr = listener.tcp_local_addr().unwrap();
+        let storage_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::new(storage_dir.path());
+        configure(&mut config);
+        task::spawn(listener.listen(sig_key_pair, config));
+
+        (tcp_addr, sig_pub_key, storage_dir)
+    }
+
+    async fn connect(addr: SocketAddr, sig_pub_key: &[u8]) -> Client<UpgradedConnection> {
+        connect_stream(
+            BaseStream::Tcp(TcpStream::connect(addr).await.unwrap()),
+            sig_pub_key,
+        )
+        .await
+    }
+
+    async fn connect_stream(stream: BaseStream, sig_pub_key: &[u8]) -> Client<UpgradedConnection> {
+        Client::new()
+            .connect(stream)
+            .handshake(ServerSigPubKey::new(sig_pub_key))
+            .await
+            .ok()
+            .unwrap()
+    }
+
+    #[async_std::test]
+    async fn test_listen_tcp_and_quic() {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = sig_key_pair.public_key().as_ref().to_owned();
+
+        let localhost = SocketAddr::from(([127, 0, 0, 1], 0));
+        let listener = Listener::bind(localhost, localhost).await.unwrap();
+        let tcp_addr = listener.tcp_local_addr().unwrap();
+        let quic_addr = listener.quic_local_addr().unwrap();
+        let storage_dir = tempfile::tempdir().unwrap();
+        task::spawn(listener.listen(sig_key_pair, Config::new(storage_dir.path())));
+
+        let tcp_stream = TcpStream::connect(tcp_addr).await.unwrap();
+        connect_stream(BaseStream::Tcp(tcp_stream), &sig_pub_key)
+            .await
+            .disconnect()
+            .await
+            .unwrap();
+
+        let mut endpoint = quinn::Endpoint::client(localhost).unwrap();
+        endpoint.set_default_client_config(quic::client_config());
+        let connection = endpoint
+            .connect(quic_addr, quic::SERVER_NAME)
+            .unwrap()
+            .await
+            .unwrap();
+        let (send_stream, recv_stream) = connection.open_bi().await.unwrap();
+        connect_stream(
+            BaseStream::Quic(QuicStream {
+                send_stream,
+                recv_stream,
+            }),
+            &sig_pub_key,
+        )
+        .await
+        .disconnect()
+        .await
+        .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_handshake_trust_on_first_use() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|_| {}).await;
+        let insecure_connect = || async {
+            Client::new().connect(BaseStream::Tcp(TcpStream::connect(tcp_addr).await.unwrap()))
+        };
+
+        let (client, presented_sig_pub_key) = insecure_connect()
+            .await
+            .handshake_trust_on_first_use()
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(presented_sig_pub_key.as_slice(), sig_pub_key);
+        client.disconnect().await.unwrap();
+
+        // The recorded key verifies later connections, and any other key fails loudly.
+        insecure_connect()
+            .await
+            .handshake(ServerSigPubKey::from(presented_sig_pub_key))
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+        let other_sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let (_, error) = insecure_connect()
+            .await
+            .handshake(ServerSigPubKey::from(crypto::identity::export_public_key(
+                &other_sig_key_pair,
+            )))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::Crypto(CryptoError::ServerSigPubKeyMismatch)
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_adjust_len_limit() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|config| {
+            config.max_len_limit = 4095;
+        })
+        .await;
+
+        let mut client = connect(tcp_addr, &sig_pub_key).await;
+        assert!(!client.adjust_len_limit(MAX_LEN_LIMIT).await.unwrap());
+        assert!(client.adjust_len_limit(4095).await.unwrap());
+        assert!(client.adjust_len_limit(2047).await.unwrap());
+
+        // Both sides keep working with the adjusted limit.
+        let data = vec![7u8; 20000];
+        let mut client = client
+            .send_resource_request(transfer::SendResourceRequest {
+                resources: vec![transfer::Resource::file(data.len() as u64, "a.bin")],
+                expiry_duration: None,
+                receiver_control: None,
+                compression: transfer::Compression::SUPPORTED.to_vec(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        client.send_resource(data.as_slice()).await.unwrap();
+        let mut client = client
+            .send_completed()
+            .await
+            .ok()
+            .unwrap()
+            .receive_resource_request(
+                transfer::ReceiveResourceRequest {
+                    id,
+                    control: None,
+                    offsets: Vec::new(),
+                    compression: transfer::Compression::SUPPORTED.to_vec(),
+                },
+                None,
+            )
+            .await
+            .ok()
+            .unwrap();
+        let mut received = Vec::new();
+        client.receive_resource(&mut received).await.unwrap();
+        assert_eq!(received, data);
+        client
+            .receive_completed()
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_key_update() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|config| {
+            config.key_update_limits = KeyUpdateLimits {
+                frames: 3,
+                bytes: u64::MAX,
+            };
+        })
+        .await;
+
+        // The keys are updated every few frames while sending, by either side.
+        let stream = BaseStream::Tcp(TcpStream::connect(tcp_addr).await.unwrap());
+        let mut client = Client::new().connect(stream);
+        client.set_key_update_limits(KeyUpdateLimits {
+            frames: u64::MAX,
+            bytes: 4096,
+        });
+        let data = (0..50000).map(|i| i as u8).collect::<Vec<_>>();
+        let mut client = client
+            .handshake(ServerSigPubKey::new(sig_pub_key))
+            .await
+            .ok()
+            .unwrap()
+            .send_resource_request(transfer::SendResourceRequest {
+                resources: vec![transfer::Resource::file(data.len() as u64, "a.bin")],
+                expiry_duration: None,
+                receiver_control: None,
+                compression: Vec::new(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        client.send_resource(data.as_slice()).await.unwrap();
+        let mut client = client
+            .send_completed()
+            .await
+            .ok()
+            .unwrap()
+            .receive_resource_request(
+                transfer::ReceiveResourceRequest {
+                    id,
+                    control: None,
+                    offsets: Vec::new(),
+                    compression: Vec::new(),
+                },
+                None,
+            )
+            .await
+            .ok()
+            .unwrap();
+        let mut received = Vec::new();
+        client.receive_resource(&mut received).await.unwrap();
+        assert_eq!(received, data);
+        client
+            .receive_completed()
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_client_auth() {
+        let client_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let other_client_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|config| {
+            config.allowed_clients =
+                Some(vec![crypto::identity::export_public_key(&client_key_pair)]);
+        })
+        .await;
+
+        let client = connect(tcp_addr, &sig_pub_key).await;
+        let request = || transfer::SendResourceRequest {
+            resources: vec![transfer::Resource::file(5, "a.txt")],
+            expiry_duration: None,
+            receiver_control: None,
+            compression: Vec::new(),
+        };
+
+        // Unauthenticated clients, and those not allowed, cannot send resources.
+        let (client, error) = client.send_resource_request(request()).await.err().unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::Unauthorized)
+        ));
+        let (client, error) = client
+            .authenticate(&other_client_key_pair)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::ClientAuth(ClientAuthError::NotAllowed)
+        ));
+        let (client, error) = client.send_resource_request(request()).await.err().unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::Unauthorized)
+        ));
+
+        let mut client = client
+            .authenticate(&client_key_pair)
+            .await
+            .ok()
+            .unwrap()
+            .send_resource_request(request())
+            .await
+            .ok()
+            .unwrap();
+        client.send_resource(b"hello".as_slice()).await.unwrap();
+        client
+            .send_completed()
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_stalled_upload() {
+        let (tcp_addr, sig_pub_key, storage_dir) = spawn_server(|config| {
+            config.upload_chunk_timeout = Duration::from_millis(100);
+        })
+        .await;
+
+        let client = connect(tcp_addr, &sig_pub_key)
+            .await
+            .send_resource_request(transfer::SendResourceRequest {
+                resources: vec![transfer::Resource::file(5, "a.txt")],
+                expiry_duration: None,
+                receiver_control: None,
+                compression: Vec::new(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        assert!(std::fs::read_dir(storage_dir.path())
+            .unwrap()
+            .next()
+            .is_some());
+
+        // The upload is discarded once the sender stalls for longer than the timeout.
+        task::sleep(Duration::from_millis(500)).await;
+        assert!(std::fs::read_dir(storage_dir.path())
+            .unwrap()
+            .next()
+            .is_none());
+        let (_, error) = connect(tcp_addr, &sig_pub_key)
+            .await
+            .receive_resource_request(
+                transfer::ReceiveResourceRequest {
+                    id,
+                    control: None,
+                    offsets: Vec::new(),
+                    compression: Vec::new(),
+                },
+                None,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::Failed)
+        ));
+        drop(client);
+    }
+
+    #[async_std::test]
+    async fn test_send_and_receive_resource() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|_| {}).await;
+
+        let control = || Some(transfer::ReceiverControl::Password("test".to_owned()));
+        let receive_request =
+            |id: &transfer::ResourceId, control| transfer::ReceiveResourceRequest {
+                id: id.clone(),
+                control,
+                offsets: Vec::new(),
+                compression: Vec::new(),
+            };
+
+        // NOTE: Directories carry no data, so only the files are transferred.
+        let resources = vec![
+            transfer::Resource::directory("dir"),
+            transfer::Resource::file(5, "dir/a.txt"),
+            transfer::Resource::directory("dir/empty"),
+            transfer::Resource::file(0, "b.txt"),
+        ];
+        let mut client = connect(tcp_addr, &sig_pub_key)
+            .await
+            .send_resource_request(transfer::SendResourceRequest {
+                resources: resources.clone(),
+                expiry_duration: None,
+                receiver_control: control(),
+                compression: transfer::Compression::SUPPORTED.to_vec(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        client.send_resource(b"hello".as_slice()).await.unwrap();
+        client.send_resource(b"".as_slice()).await.unwrap();
+        client
+            .send_completed()
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+
+        let client = connect(tcp_addr, &sig_pub_key).await;
+        let (client, _) = client
+            .receive_resource_request(receive_request(&id, None), None)
+            .await
+            .err()
+            .unwrap();
+        let mut client = client
+            .receive_resource_request(receive_request(&id, control()), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(client.resources(), resources.as_slice());
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        client.receive_resource(&mut a).await.unwrap();
+        client.receive_resource(&mut b).await.unwrap();
+        assert_eq!(
+            (a.as_slice(), b.as_slice()),
+            (b"hello".as_slice(), b"".as_slice())
+        );
+        let client = client.receive_completed().await.ok().unwrap();
+
+        // The resource can only be received once.
+        let (client, _) = client
+            .receive_resource_request(receive_request(&id, control()), None)
+            .await
+            .err()
+            .unwrap();
+        client.disconnect().await.unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_resume_receive() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|_| {}).await;
+
+        let resources = vec![
+            transfer::Resource::file(11, "a.txt"),
+            transfer::Resource::directory("dir"),
+            transfer::Resource::file(3, "dir/b.txt"),
+        ];
+        let mut client = connect(tcp_addr, &sig_pub_key)
+            .await
+            .send_resource_request(transfer::SendResourceRequest {
+                resources,
+                expiry_duration: None,
+                receiver_control: None,
+                compression: transfer::Compression::SUPPORTED.to_vec(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        client
+            .send_resource(b"hello world".as_slice())
+            .await
+            .unwrap();
+        client.send_resource(b"abc".as_slice()).await.unwrap();
+        let client = client.send_completed().await.ok().unwrap();
+        let receive_request = |offsets| transfer::ReceiveResourceRequest {
+            id: id.clone(),
+            control: None,
+            offsets,
+            compression: transfer::Compression::SUPPORTED.to_vec(),
+        };
+
+        let (client, error) = client
+            .receive_resource_request(receive_request(vec![12, 0, 0]), None)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::InvalidOffsets)
+        ));
+
+        // The receiver drops the connection halfway through the download.
+        let mut client = client
+            .receive_resource_request(receive_request(Vec::new()), None)
+            .await
+            .ok()
+            .unwrap();
+        let mut a = Vec::new();
+        client.receive_resource(&mut a).await.unwrap();
+        drop(client);
+
+        // NOTE: The server only makes the resource available again once it notices
+        // the dropped connection.
+        let mut client = loop {
+            match connect(tcp_addr, &sig_pub_key)
+                .await
+                .receive_resource_request(receive_request(vec![6, 0, 1]), None)
+                .await
+            {
+                Ok(client) => break client,
+                Err(_) => task::sleep(std::time::Duration::from_millis(50)).await,
+            }
+        };
+        // The partial files are read to verify the digest of the whole files.
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        client
+            .resume_resource(b"hello ".as_slice(), &mut a)
+            .await
+            .unwrap();
+        client
+            .resume_resource(b"a".as_slice(), &mut b)
+            .await
+            .unwrap();
+        assert_eq!(
+            (a.as_slice(), b.as_slice()),
+            (b"world".as_slice(), b"bc".as_slice())
+        );
+        client
+            .receive_completed()
+            .await
+            .ok()
+            .unwrap()
+            .disconnect()
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_receive_resource_with_public_key() {
+        let (tcp_addr, sig_pub_key, _storage_dir) = spawn_server(|_| {}).await;
+
+        let receiver_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let other_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let control = || {
+            Some(transfer::ReceiverControl::PublicKey(
+                receiver_key_pair.public_key().as_ref().try_into().unwrap(),
+            ))
+        };
+
+        let mut client = connect(tcp_addr, &sig_pub_key)
+            .await
+            .send_resource_request(transfer::SendResourceRequest {
+                resources: vec![transfer::Resource::file(5, "a.txt")],
+                expiry_duration: None,
+                receiver_control: control(),
+                compression: Vec::new(),
+            })
+            .await
+            .ok()
+            .unwrap();
+        let id = client.resource_id().clone();
+        client.send_resource(b"hello".as_slice()).await.unwrap();
+        client.send_completed().await.ok().unwrap();
+
+        let receive_request = || transfer::ReceiveResourceRequest {
+            id: id.clone(),
+            control: control(),
+            offsets: Vec::new(),
+            compression: Vec::new(),
+        };
+
+        // Without the key pair the challenge cannot be answered, but the client stays usable.
+        let (client, error) = connect(tcp_addr, &sig_pub_key)
+            .await
+            .receive_resource_request(receive_request(), None)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::MissingReceiverKeyPair)
+        ));
+
+        // Neither a missing resource nor another public key is revealed, as the receiver is
+        // challenged all the same.
+        let missing_request = transfer::ReceiveResourceRequest {
+            id: transfer::ResourceId(vec![0; id.0.len()]),
+            ..receive_request()
+        };
+        let other_request = transfer::ReceiveResourceRequest {
+            control: Some(transfer::ReceiverControl::PublicKey(
+                other_key_pair.public_key().as_ref().try_into().unwrap(),
+            )),
+            ..receive_request()
+        };
+        let mut client = client;
+        for request in [missing_request, other_request] {
+            let (next_client, error) = client
+                .receive_resource_request(request, None)
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(
+                error,
+                Error::ResourceRequest(ResourceRequestError::MissingReceiverKeyPair)
+            ));
+            client = next_client;
+        }
+
+        // Knowing the public key alone is not enough.
+        let (client, error) = client
+            .receive_resource_request(receive_request(), Some(&other_key_pair))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::ResourceRequest(ResourceRequestError::Failed)
+        ));
+
+        let mut client = client
+            .receive_resource_request(receive_request(), Some(&receiver_key_pair))
+            .await
+            .ok()
+            .unwrap();
+        let mut data = Vec::new();
+        client.receive_resource(&mut data).await.unwrap();
+        assert_eq!(data, b"hello");
+        client.receive_completed().await.ok().unwrap();
+    }
+}
@@ -1 +1,351 @@
+mod allocator;
+mod config;
+mod listener;
+mod reaper;
+mod session;
+mod state;
+mod storage;
 
+use ring::agreement;
+use ring::signature::Ed25519KeyPair;
+
+use crate::crypto::{self, CipherSuite};
+use crate::error::Error;
+use crate::proto::message::{auth, handshake, Message, Secure};
+use crate::proto::stream::{BaseStream, Plain, PlainStream};
+use crate::proto::{self, Side};
+use crate::state::{PlainState, SecureState, State};
+
+use self::state::*;
+
+pub use self::config::Config;
+pub use self::listener::Listener;
+pub use self::storage::DownloadState;
+
+pub struct Server<T: State> {
+    state: T,
+}
+
+impl Server<NoConnection> {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: NoConnection,
+        }
+    }
+
+    pub(crate) fn connect(self, stream: BaseStream) -> Server<InsecureConnection> {
+        Server {
+            state: InsecureConnection::new(PlainStream::from(stream)),
+        }
+    }
+}
+
+impl Server<InsecureConnection> {
+    pub(crate) async fn client_hello(
+        mut self,
+        client_hello_msg: handshake::ClientHelloMessage,
+        allowed_suites: &[CipherSuite],
+    ) -> Result<Server<HandshakingConnection>, (Self, Error)> {
+        let client_hello_result = async {
+            let negotiated = match (
+                proto::negotiate_version(&client_hello_msg.versions),
+                crypto::suite::negotiate(&client_hello_msg.suites, allowed_suites),
+            ) {
+                (Some(version), Some(suite)) => Ok((version, suite)),
+                (None, _) => Err(handshake::HandshakeFailure::NoCommonVersion),
+                (_, None) => Err(handshake::HandshakeFailure::NoCommonCipherSuite),
+            };
+            let (version, suite) = match negotiated {
+                Ok(negotiated) => negotiated,
+                Err(failure) => {
+                    self.state
+                        .plain_stream()
+                        .send(handshake::HandshakeFailureMessage::from(failure).into())
+                        .await?;
+                    return Err(failure.into());
+                }
+            };
+
+            // Generate server nonce
+            let server_nonce = crypto::generate_nonce().await?;
+
+            // Generate ephemeral key pair
+            let (server_private_key, public_key) = crypto::generate_ephemeral_key_pair()?;
+
+            Ok::<HandshakeContext, Error>(HandshakeContext {
+                client_hello_msg,
+                version,
+                suite,
+                nonce: server_nonce,
+                private_key: server_private_key,
+                // SAFETY: public key has the correct length
+                public_key_bytes: <[u8; crypto::X25519_PUBLIC_KEY_LEN]>::try_from(
+                    public_key.as_ref(),
+                )
+                .unwrap(),
+            })
+        }
+        .await;
+
+        match client_hello_result {
+            Ok(handshake_context) => Ok(Server {
+                state: HandshakingConnection::new(self.state, handshake_context),
+            }),
+            Err(error) => Err((self, error)),
+        }
+    }
+}
+
+impl Server<HandshakingConnection> {
+    pub(crate) async fn server_hello(
+        mut self,
+        server_sig_key_pair: &Ed25519KeyPair,
+    ) -> Result<Server<UpgradedConnection>, (Server<InsecureConnection>, Error)> {
+        let server_hello_result = async {
+            // SAFETY: private key has not been taken out before
+            let HandshakeContext {
+                client_hello_msg,
+                version,
+                suite,
+                nonce: server_nonce,
+                private_key: server_private_key,
+                public_key_bytes: server_public_key_bytes,
+            } = self.state.context().unwrap();
+
+            // Sign
+            let (server_hello_msg, nonces) = crypto::sign_server_hello(
+                &client_hello_msg,
+                server_nonce,
+                server_public_key_bytes,
+                version,
+                suite,
+                server_sig_key_pair,
+            );
+
+            let plain_stream = self.state.plain_stream();
+            plain_stream.send(server_hello_msg.into()).await?;
+            plain_stream.set_version(version);
+
+            // Generate session secrets
+            let session_secrets = crypto::generate_session_secrets(
+                server_private_key,
+                agreement::UnparsedPublicKey::new(
+                    &agreement::X25519,
+                    client_hello_msg.public_key_bytes,
+                ),
+                nonces,
+                suite,
+                crypto::handshake_transcript(&client_hello_msg, &server_hello_msg),
+                Side::Server,
+            )
+            .await?;
+
+            Ok::<crypto::secrets::SessionSecrets, Error>(session_secrets)
+        }
+        .await;
+
+        match server_hello_result {
+            Ok(session_secrets) => Ok(Server {
+                state: UpgradedConnection::new(self.state, session_secrets),
+            }),
+            Err(error) => Err((
+                Server {
+                    state: self.state.failed(),
+                },
+                error,
+            )),
+        }
+    }
+}
+
+impl Server<UpgradedConnection> {
+    // Verify the client's signature over the handshake transcript, and record its identity
+    // if its key is allowed.
+    // NOTE: The signature is checked first, such that the allowlist cannot be probed with
+    // the keys of others.
+    pub(crate) async fn client_auth(
+        &mut self,
+        request: auth::ClientAuthRequest,
+        allowed_clients: Option<&[[u8; crypto::ED25519_PUBLIC_KEY_LEN]]>,
+    ) -> Result<(), Error> {
+        let secure_stream = self.state.secure_stream();
+        let response = if crypto::verify_client_auth(
+            secure_stream.session_secrets(),
+            &request.public_key,
+            &request.signature,
+        )
+        .is_err()
+        {
+            auth::ClientAuthResponse::BadSignature
+        } else if allowed_clients.is_some_and(|allowed| !allowed.contains(&request.public_key)) {
+            auth::ClientAuthResponse::NotAllowed
+        } else {
+            auth::ClientAuthResponse::Ok
+        };
+        response.send(secure_stream).await?;
+
+        if response == auth::ClientAuthResponse::Ok {
+            self.state.authenticated(request.public_key);
+        }
+        Ok(())
+    }
+}
+
+impl<T: PlainState> Server<T> {
+    pub(crate) async fn recv(&mut self) -> Result<Message, Error> {
+        self.state.plain_stream().recv().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::net::{TcpListener, TcpStream};
+    use async_std::task;
+    use ring::signature::{self, KeyPair};
+
+    use super::*;
+    use crate::proto::message::{transfer, Secure};
+    use crate::proto::stream::SecureStream;
+    use crate::state::SecureState;
+
+    #[async_std::test]
+    async fn test_server_handshake() {
+        let sig_key_pair = crypto::generate_signature_key_pair().unwrap();
+        let sig_pub_key = signature::UnparsedPublicKey::new(
+            &signature::ED25519,
+            sig_key_pair.public_key().as_ref().to_owned(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let request = transfer::SendResourceRequest {
+            resources: vec![transfer::Resource::file(1024, "test.txt")],
+            expiry_duration: None,
+            receiver_control: None,
+            compression: Vec::new(),
+        };
+
+        task::spawn(async move {
+            let mut stream =
+                PlainStream::from(BaseStream::Tcp(TcpStream::connect(addr).await.unwrap()));
+
+            let (private_key, public_key) = crypto::generate_ephemeral_key_pair().unwrap();
+            let nonce = crypto::generate_nonce().await.unwrap();
+            let versions = proto::encode_versions(proto::SUPPORTED_PROTOCOL_VERSIONS);
+            let msg = handshake::ClientHelloMessage {
+                nonce,
+                public_key_bytes: public_key.as_ref().try_into().unwrap(),
+                versions,
+                suites: crypto::suite::encode(&[CipherSuite::ChaCha20Poly1305]),
+            };
+            stream.send(msg.into()).await.unwrap();
+
+            let server_hello_msg =
+                handshake::ServerHelloMessage::try_from(stream.recv().await.unwrap()).unwrap();
+            assert_eq!(
+                server_hello_msg.suite,
+                [CipherSuite::ChaCha20Poly1305.into()]
+            );
+            let (server_public_key, nonces) =
+                crypto::verify_server_hello(server_hello_msg, &msg, &sig_pub_key).unwrap();
+            stream.set_version(proto::CURRENT_PROTOCOL_VERSION);
+            let session_secrets = crypto::generate_session_secrets(
+                private_key,
+                server_public_key,
+                nonces,
+                CipherSuite::ChaCha20Poly1305,
+                crypto::handshake_transcript(&msg, &server_hello_msg),
+                Side::Client,
+            )
+            .await
+            .unwrap();
+
+            transfer::SendResourceRequest {
+                resources: vec![transfer::Resource::file(1024, "test.txt")],
+                expiry_duration: None,
+                receiver_control: None,
+                compression: Vec::new(),
+            }
+            .send(&mut SecureStream::new(stream, session_secrets))
+            .await
+            .unwrap();
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = Server::new().connect(BaseStream::Tcp(stream));
+
+        let msg = server.recv().await.unwrap();
+        let client_hello_msg = handshake::ClientHelloMessage::try_from(msg).unwrap();
+
+        let server = server
+            .client_hello(client_hello_msg, CipherSuite::SUPPORTED)
+            .await
+            .ok()
+            .unwrap();
+        let mut server = server.server_hello(&sig_key_pair).await.ok().unwrap();
+
+        assert_eq!(
+            transfer::SendResourceRequest::recv(server.state.secure_stream())
+                .await
+                .unwrap(),
+            request
+        );
+    }
+
+    #[async_std::test]
+    async fn test_server_handshake_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        for (versions, suites, failure) in [
+            // An unknown version only.
+            (
+                [0xff, 0x00, 0x00, 0x00],
+                crypto::suite::encode(CipherSuite::SUPPORTED),
+                handshake::HandshakeFailure::NoCommonVersion,
+            ),
+            // A cipher suite which the server does not allow.
+            (
+                proto::encode_versions(proto::SUPPORTED_PROTOCOL_VERSIONS),
+                crypto::suite::encode(&[CipherSuite::Aes128Gcm]),
+                handshake::HandshakeFailure::NoCommonCipherSuite,
+            ),
+        ] {
+            let client = task::spawn(async move {
+                let mut stream =
+                    PlainStream::from(BaseStream::Tcp(TcpStream::connect(addr).await.unwrap()));
+
+                let (_, public_key) = crypto::generate_ephemeral_key_pair().unwrap();
+                let msg = handshake::ClientHelloMessage {
+                    nonce: crypto::generate_nonce().await.unwrap(),
+                    public_key_bytes: public_key.as_ref().try_into().unwrap(),
+                    versions,
+                    suites,
+                };
+                stream.send(msg.into()).await.unwrap();
+
+                let msg =
+                    handshake::HandshakeFailureMessage::try_from(stream.recv().await.unwrap())
+                        .unwrap();
+                handshake::HandshakeFailure::try_from(msg).unwrap()
+            });
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = Server::new().connect(BaseStream::Tcp(stream));
+
+            let msg = server.recv().await.unwrap();
+            let client_hello_msg = handshake::ClientHelloMessage::try_from(msg).unwrap();
+
+            let error = match server
+                .client_hello(client_hello_msg, &[CipherSuite::ChaCha20Poly1305])
+                .await
+            {
+                Err((_, error)) => error,
+                Ok(_) => panic!("handshake should fail"),
+            };
+            assert_eq!(error.to_string(), Error::from(failure).to_string());
+            assert_eq!(client.await, failure);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::future;
+
+use super::session::Context;
+use crate::error::Error;
+
+// NOTE: The reaper wakes up at least this often, in case the system clock has jumped.
+const MAX_SLEEP_DURATION: Duration = Duration::from_secs(60);
+
+// Delete the resources once they expire. Receive requests for expired resources are
+// rejected by the session regardless, so deleting them slightly late is harmless.
+pub(super) async fn reap(context: Arc<Context>) -> Result<(), Error> {
+    let storage = &context.storage;
+
+    loop {
+        let now = chrono::Utc::now();
+        for id in storage.expired(now).await {
+            match storage.remove(&id).await {
+                Ok(()) => tracing::info!("Removed expired resource {}", id),
+                Err(error) => tracing::warn!("Failed to remove expired resource {}: {}", id, error),
+            }
+        }
+
+        let sleep_duration = storage
+            .next_expiry()
+            .await
+            .and_then(|expiry| (expiry - now).to_std().ok())
+            .map_or(MAX_SLEEP_DURATION, |duration| {
+                duration.min(MAX_SLEEP_DURATION)
+            });
+        future::timeout(sleep_duration, storage.changed())
+            .await
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_std::task;
+
+    use super::*;
+    use crate::crypto;
+    use crate::proto::message::transfer::ResourceId;
+    use crate::server::config::Config;
+    use crate::server::storage::{DownloadState, ResourceMetadata, Storage};
+
+    #[async_std::test]
+    async fn test_reap() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = Arc::new(Context {
+            sig_key_pair: crypto::generate_signature_key_pair().unwrap(),
+            storage: Storage::open(dir.path()).await.unwrap(),
+            config: Config::new(dir.path()),
+        });
+        let storage = &context.storage;
+        let metadata = |id: Vec<u8>, expiry_duration: chrono::Duration| ResourceMetadata {
+            id: ResourceId(id),
+            resources: vec![],
+            digests: vec![],
+            receiver_control: None,
+            expiry: chrono::Utc::now() + expiry_duration,
+            state: DownloadState::Uploading,
+        };
+
+        let expired = metadata(vec![1, 1], chrono::Duration::zero());
+        storage.insert(expired.clone()).await.unwrap();
+        storage.complete_upload(&expired.id, vec![]).await.unwrap();
+        task::spawn(reap(context.clone()));
+
+        // A resource inserted later with an earlier expiry reschedules the reaper.
+        let expiring = metadata(vec![2, 2], chrono::Duration::milliseconds(200));
+        let remaining = metadata(vec![3, 3], chrono::Duration::hours(1));
+        for metadata in [&expiring, &remaining] {
+            storage.insert(metadata.clone()).await.unwrap();
+            storage.complete_upload(&metadata.id, vec![]).await.unwrap();
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+        assert!(storage.get(&expired.id).await.is_none());
+        assert!(storage.get(&expiring.id).await.is_some());
+
+        task::sleep(Duration::from_millis(300)).await;
+        assert!(storage.get(&expiring.id).await.is_none());
+        assert!(storage.get(&remaining.id).await.is_some());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,402 @@
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use async_std::io::prelude::SeekExt;
+use ring::signature::{self, Ed25519KeyPair};
+
+use super::allocator;
+use super::config::Config;
+use super::storage::{DownloadState, ResourceMetadata, Storage, StoredReceiverControl};
+use super::Server;
+use crate::crypto;
+use crate::crypto::password::PasswordVerifier;
+use crate::error::{Error, InvalidMessageError};
+use crate::proto::message::{
+    auth, compression, handshake, len_limit, recv_header, transfer, Message, PlainMessageType,
+    Secure, SecureMessageType, MAX_LEN_LIMIT, MIN_LEN_LIMIT,
+};
+use crate::proto::stream::{BaseStream, Plain, SecureStream};
+use crate::state::SecureState;
+
+// The state shared by all sessions of a server.
+pub(super) struct Context {
+    pub(super) sig_key_pair: Ed25519KeyPair,
+    pub(super) storage: Storage,
+    pub(super) config: Config,
+}
+
+pub(super) async fn serve(stream: BaseStream, context: Arc<Context>) -> Result<(), Error> {
+    let context = context.as_ref();
+    let mut server = Server::new().connect(stream);
+
+    let client_hello_msg = handshake::ClientHelloMessage::try_from(server.recv().await?)?;
+    let server = server
+        .client_hello(client_hello_msg, &context.config.cipher_suites)
+        .await
+        .map_err(|(_, error)| error)?;
+    let mut server = server
+        .server_hello(&context.sig_key_pair)
+        .await
+        .map_err(|(_, error)| error)?;
+    server
+        .state
+        .secure_stream()
+        .set_key_update_limits(context.config.key_update_limits);
+
+    loop {
+        let secure_stream = server.state.secure_stream();
+        // NOTE: In between requests, the client may also adjust the length limit or
+        // disconnect with a plain message.
+        let msg = secure_stream.recv().await?;
+        match msg.plain_msg_type() {
+            PlainMessageType::Secure => secure_stream.buffer(msg)?,
+            PlainMessageType::AdjustLenLimitRequest => {
+                respond_len_limit(secure_stream, msg, &context.config).await?;
+                continue;
+            }
+            PlainMessageType::Disconnect => return Ok(()),
+            others => return Err(InvalidMessageError::UnexpectedMessageType(others).into()),
+        }
+
+        match recv_header(secure_stream).await? {
+            SecureMessageType::ClientAuthRequest => {
+                let request = auth::ClientAuthRequest::recv_body(secure_stream).await?;
+                server
+                    .client_auth(request, context.config.allowed_clients.as_deref())
+                    .await?;
+            }
+            SecureMessageType::SendResourceRequest => {
+                let request = transfer::SendResourceRequest::recv_body(secure_stream).await?;
+                let client_identity = server.state.client_identity().copied();
+                handle_send_request(
+                    server.state.secure_stream(),
+                    context,
+                    request,
+                    client_identity,
+                )
+                .await?;
+            }
+            SecureMessageType::ReceiveResourceRequest => {
+                let request = transfer::ReceiveResourceRequest::recv_body(secure_stream).await?;
+                handle_receive_request(secure_stream, context, request).await?;
+            }
+            others => return Err(InvalidMessageError::UnexpectedSecureMessageType(others).into()),
+        }
+    }
+}
+
+// READ: proto/message/msg_len_limit.md for more information.
+async fn respond_len_limit(
+    secure_stream: &mut SecureStream,
+    msg: Message,
+    config: &Config,
+) -> Result<(), Error> {
+    let len_limit = len_limit::AdjustLenLimitRequest::try_from(msg)?.len_limit();
+    let has_accepted = (MIN_LEN_LIMIT..=MAX_LEN_LIMIT).contains(&len_limit)
+        && (len_limit <= secure_stream.len_limit() || len_limit <= config.max_len_limit);
+
+    secure_stream
+        .send(len_limit::AdjustLenLimitResponse::new(has_accepted).into())
+        .await?;
+    // NOTE: The limit only changes after the response has been sent.
+    if has_accepted {
+        secure_stream.set_len_limit(len_limit);
+    }
+
+    Ok(())
+}
+
+// Clamp the suggested expiry duration to the configured range, or return `None` if it
+// cannot be honoured at all.
+fn expiry(
+    expiry_duration: Option<chrono::Duration>,
+    config: &Config,
+) -> Option<(chrono::Duration, chrono::DateTime<chrono::Utc>)> {
+    let expiry_duration = match expiry_duration {
+        Some(duration) if duration <= chrono::Duration::zero() => return None,
+        Some(duration) => duration
+            .max(config.min_expiry_duration)
+            .min(config.max_expiry_duration),
+        None => config.expiry_duration,
+    };
+    let expiry = chrono::Utc::now().checked_add_signed(expiry_duration)?;
+
+    Some((expiry_duration, expiry))
+}
+
+async fn handle_send_request(
+    secure_stream: &mut SecureStream,
+    context: &Context,
+    request: transfer::SendResourceRequest,
+    client_identity: Option<[u8; crypto::ED25519_PUBLIC_KEY_LEN]>,
+) -> Result<(), Error> {
+    // NOTE: With an allowlist, only the clients authenticated against it may send resources.
+    if context.config.allowed_clients.is_some() && client_identity.is_none() {
+        return transfer::SendResourceResponse::Unauthorized
+            .send(secure_stream)
+            .await;
+    }
+
+    if transfer::validate_manifest(&request.resources).is_err() {
+        return transfer::SendResourceResponse::InvalidManifest
+            .send(secure_stream)
+            .await;
+    }
+
+    let total_size = request
+        .resources
+        .iter()
+        .try_fold(0u64, |total, resource| total.checked_add(resource.size));
+    if total_size.is_none_or(|size| size > context.config.max_resource_size) {
+        return transfer::SendResourceResponse::ResourceTooLarge
+            .send(secure_stream)
+            .await;
+    }
+
+    let Some((expiry_duration, expiry)) = expiry(request.expiry_duration, &context.config) else {
+        return transfer::SendResourceResponse::InvalidExpiry
+            .send(secure_stream)
+            .await;
+    };
+
+    let receiver_control = match request.receiver_control {
+        Some(transfer::ReceiverControl::Password(password)) if password.is_empty() => {
+            return transfer::SendResourceResponse::InvalidReceiverControl
+                .send(secure_stream)
+                .await;
+        }
+        Some(transfer::ReceiverControl::Password(password)) => Some(
+            StoredReceiverControl::Password(PasswordVerifier::new(password).await?),
+        ),
+        Some(transfer::ReceiverControl::PublicKey(public_key)) => {
+            Some(StoredReceiverControl::PublicKey(public_key))
+        }
+        None => None,
+    };
+
+    let id = allocator::allocate(&context.storage, expiry_duration, &context.config, |id| {
+        ResourceMetadata {
+            id,
+            resources: request.resources.clone(),
+            digests: Vec::new(),
+            receiver_control: receiver_control.clone(),
+            expiry,
+            state: DownloadState::Uploading,
+        }
+    })
+    .await?;
+
+    let compression = compression::negotiate(&request.compression);
+    let upload_result = async {
+        transfer::SendResourceResponse::Ok {
+            id: id.clone(),
+            expiry,
+            compression,
+        }
+        .send(secure_stream)
+        .await?;
+        secure_stream.set_compression(compression);
+        // NOTE: A sender stalling mid-upload would otherwise keep the resource from being
+        // reaped forever.
+        secure_stream.set_recv_timeout(Some(context.config.upload_chunk_timeout));
+
+        let mut digests = Vec::with_capacity(request.resources.len());
+        for (index, resource) in request.resources.iter().enumerate() {
+            if resource.is_dir() {
+                digests.push(None);
+                continue;
+            }
+            let blob = context.storage.create_blob(&id, index).await?;
+            let digest = transfer::recv_resource(
+                secure_stream,
+                resource.size,
+                blob,
+                crypto::ResourceHasher::new(),
+                |_| {},
+            )
+            .await?;
+            digests.push(Some(digest));
+        }
+
+        context.storage.complete_upload(&id, digests).await?;
+        transfer::ResourceAck.send(secure_stream).await
+    }
+    .await;
+    secure_stream.set_compression(None);
+    secure_stream.set_recv_timeout(None);
+
+    if upload_result.is_err() {
+        if let Err(error) = context.storage.remove(&id).await {
+            tracing::warn!("Failed to remove incomplete upload: {}", error);
+        }
+    }
+    upload_result
+}
+
+// NOTE: A receiver offering a public key is challenged unless the resource is unlocked, even
+// when the resource does not exist or is locked otherwise, such that the reply does not reveal
+// whether the resource exists or which public key it is locked with.
+async fn verify_receiver_control(
+    secure_stream: &mut SecureStream,
+    resource_id: &transfer::ResourceId,
+    stored: Option<&Option<StoredReceiverControl>>,
+    control: Option<transfer::ReceiverControl>,
+) -> Result<bool, Error> {
+    match (stored, control) {
+        (Some(None), _) => Ok(true),
+        (stored, Some(transfer::ReceiverControl::PublicKey(_))) => {
+            let stored_public_key = match stored {
+                Some(Some(StoredReceiverControl::PublicKey(public_key))) => Some(public_key),
+                _ => None,
+            };
+            challenge_receiver(secure_stream, resource_id, stored_public_key).await
+        }
+        (
+            Some(Some(StoredReceiverControl::Password(verifier))),
+            Some(transfer::ReceiverControl::Password(password)),
+        ) => Ok(verifier.verify(password).await),
+        _ => Ok(false),
+    }
+}
+
+// Challenge the receiver to prove possession of the private key. Without a public key to
+// verify against, the challenge fails regardless of the response.
+async fn challenge_receiver(
+    secure_stream: &mut SecureStream,
+    resource_id: &transfer::ResourceId,
+    public_key: Option<&[u8; signature::ED25519_PUBLIC_KEY_LEN]>,
+) -> Result<bool, Error> {
+    let mut challenge = [0u8; crypto::RECEIVER_CHALLENGE_LEN];
+    crypto::fill_random(&mut challenge)?;
+    transfer::ReceiverChallenge { challenge }
+        .send(secure_stream)
+        .await?;
+
+    let transfer::ReceiverChallengeResponse { signature } =
+        transfer::ReceiverChallengeResponse::recv(secure_stream).await?;
+
+    Ok(public_key.is_some_and(|public_key| {
+        crypto::verify_receiver_challenge(
+            secure_stream.session_secrets(),
+            &resource_id.0,
+            &challenge,
+            public_key,
+            &signature,
+        )
+        .is_ok()
+    }))
+}
+
+async fn handle_receive_request(
+    secure_stream: &mut SecureStream,
+    context: &Context,
+    request: transfer::ReceiveResourceRequest,
+) -> Result<(), Error> {
+    // NOTE: A missing or expired resource is only rejected after verifying the receiver
+    // control, such that the reply does not reveal whether the resource exists.
+    let metadata = context
+        .storage
+        .get(&request.id)
+        .await
+        .filter(|metadata| metadata.expiry > chrono::Utc::now());
+    let verified = verify_receiver_control(
+        secure_stream,
+        &request.id,
+        metadata.as_ref().map(|metadata| &metadata.receiver_control),
+        request.control,
+    )
+    .await?;
+    // NOTE: The expiry is checked again, as verifying the receiver control takes time.
+    let metadata = match metadata {
+        Some(metadata) if verified && metadata.expiry > chrono::Utc::now() => metadata,
+        _ => {
+            return transfer::ReceiveResourceResponse::Failed
+                .send(secure_stream)
+                .await
+        }
+    };
+    if !transfer::valid_offsets(&metadata.resources, &request.offsets) {
+        return transfer::ReceiveResourceResponse::InvalidOffsets
+            .send(secure_stream)
+            .await;
+    }
+    if context.storage.begin_download(&metadata.id).await.is_err() {
+        return transfer::ReceiveResourceResponse::Failed
+            .send(secure_stream)
+            .await;
+    }
+
+    let compression = compression::negotiate(&request.compression);
+    let download_result = async {
+        transfer::ReceiveResourceResponse::Ok {
+            resources: metadata.resources.clone(),
+            digests: metadata.digests.clone(),
+            expiry: metadata.expiry,
+            compression,
+        }
+        .send(secure_stream)
+        .await?;
+        secure_stream.set_compression(compression);
+
+        for (index, resource) in metadata.resources.iter().enumerate() {
+            if resource.is_dir() {
+                continue;
+            }
+            let offset = request.offsets.get(index).copied().unwrap_or(0);
+            let mut blob = context.storage.open_blob(&metadata.id, index).await?;
+            blob.seek(SeekFrom::Start(offset)).await?;
+            // NOTE: The stored digest is sent, such that the receiver also detects data
+            // that has changed in storage.
+            transfer::send_resource(
+                secure_stream,
+                resource.size - offset,
+                blob,
+                metadata.digests.get(index).copied().flatten(),
+                |_| {},
+            )
+            .await?;
+        }
+
+        transfer::ResourceAck::recv(secure_stream).await?;
+        context.storage.complete_download(&metadata.id).await
+    }
+    .await;
+    secure_stream.set_compression(None);
+
+    if download_result.is_err() {
+        if let Err(error) = context.storage.abort_download(&metadata.id).await {
+            tracing::warn!("Failed to abort download: {}", error);
+        }
+    }
+    download_result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expiry() {
+        let config = Config::new("");
+        let duration = |duration| expiry(duration, &config).map(|(duration, _)| duration);
+
+        assert_eq!(duration(None), Some(config.expiry_duration));
+        assert_eq!(
+            duration(Some(chrono::Duration::hours(2))),
+            Some(chrono::Duration::hours(2))
+        );
+        assert_eq!(
+            duration(Some(chrono::Duration::seconds(1))),
+            Some(config.min_expiry_duration)
+        );
+        assert_eq!(
+            duration(Some(chrono::Duration::weeks(100))),
+            Some(config.max_expiry_duration)
+        );
+        assert_eq!(duration(Some(chrono::Duration::zero())), None);
+        assert_eq!(duration(Some(-chrono::Duration::hours(1))), None);
+
+        let (_, expiry) = expiry(None, &config).unwrap();
+        assert!(expiry > chrono::Utc::now());
+    }
+}
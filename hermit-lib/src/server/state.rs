@@ -0,0 +1,63 @@
+use ring::agreement;
+
+use crate::crypto::secrets::SessionSecrets;
+use crate::crypto::{CipherSuite, ED25519_PUBLIC_KEY_LEN, NONCE_LEN, X25519_PUBLIC_KEY_LEN};
+use crate::proto::message::handshake::ClientHelloMessage;
+use crate::proto::stream::{PlainStream, Secure, SecureStream};
+use crate::proto::ProtocolVersion;
+use crate::state::{PlainState, SecureState, State};
+use crate::{nil, plain, secure};
+
+pub struct NoConnection;
+nil!(NoConnection);
+
+pub struct InsecureConnection(PlainStream);
+plain!(InsecureConnection);
+impl InsecureConnection {
+    pub(super) fn new(stream: PlainStream) -> Self {
+        Self(stream)
+    }
+}
+
+pub(super) struct HandshakeContext {
+    pub(super) client_hello_msg: ClientHelloMessage,
+    pub(super) version: ProtocolVersion,
+    pub(super) suite: CipherSuite,
+    pub(super) nonce: [u8; NONCE_LEN],
+    pub(super) private_key: agreement::EphemeralPrivateKey,
+    pub(super) public_key_bytes: [u8; X25519_PUBLIC_KEY_LEN],
+}
+
+pub struct HandshakingConnection(PlainStream, Option<HandshakeContext>);
+plain!(HandshakingConnection);
+impl HandshakingConnection {
+    pub(super) fn new(state: InsecureConnection, handshake_context: HandshakeContext) -> Self {
+        Self(state.0, Some(handshake_context))
+    }
+
+    pub(super) fn context(&mut self) -> Option<HandshakeContext> {
+        self.1.take()
+    }
+
+    pub(super) fn failed(self) -> InsecureConnection {
+        InsecureConnection::new(self.0)
+    }
+}
+
+// NOTE: The client identity is the signature public key of a client which has
+// authenticated itself, if any.
+pub struct UpgradedConnection(SecureStream, Option<[u8; ED25519_PUBLIC_KEY_LEN]>);
+secure!(UpgradedConnection);
+impl UpgradedConnection {
+    pub(super) fn new(state: HandshakingConnection, session_secrets: SessionSecrets) -> Self {
+        Self(SecureStream::new(state.0, session_secrets), None)
+    }
+
+    pub fn client_identity(&self) -> Option<&[u8; ED25519_PUBLIC_KEY_LEN]> {
+        self.1.as_ref()
+    }
+
+    pub(super) fn authenticated(&mut self, client_public_key: [u8; ED25519_PUBLIC_KEY_LEN]) {
+        self.1 = Some(client_public_key);
+    }
+}
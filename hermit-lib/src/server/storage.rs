@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use async_std::channel;
+use async_std::fs;
+use async_std::prelude::*;
+use async_std::sync::Mutex;
+use ring::signature;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::password::PasswordVerifier;
+use crate::crypto::RESOURCE_DIGEST_LEN;
+use crate::error::{Error, StorageError};
+use crate::proto::message::transfer::{Resource, ResourceId};
+
+const METADATA_FILE_NAME: &str = "metadata";
+const METADATA_TEMP_FILE_NAME: &str = "metadata.tmp";
+
+// LAYOUT:
+// storage_dir/
+//   <hex encoded resource ID>/
+//     metadata     CBOR-encoded `ResourceMetadata`
+//     0, 1, ...    the files in the declared order, named by their index in the manifest
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadState {
+    // The resources are being uploaded by the sender.
+    Uploading,
+    // The resources are ready to be received.
+    Available,
+    // The resources are being received by a receiver.
+    // NOTE: This state is never persisted; it falls back to `Available` after a restart.
+    Downloading,
+    // The resources have been received and their blobs deleted.
+    Received,
+}
+
+// The receiver control as stored on disk.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(super) enum StoredReceiverControl {
+    Password(PasswordVerifier),
+    PublicKey([u8; signature::ED25519_PUBLIC_KEY_LEN]),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub(super) struct ResourceMetadata {
+    pub(super) id: ResourceId,
+    // The declared manifest.
+    pub(super) resources: Vec<Resource>,
+    // The digest of each file sent, and none for directories. Empty while uploading.
+    pub(super) digests: Vec<Option<[u8; RESOURCE_DIGEST_LEN]>>,
+    pub(super) receiver_control: Option<StoredReceiverControl>,
+    // The actual expiry time returned to the sender.
+    pub(super) expiry: chrono::DateTime<chrono::Utc>,
+    pub(super) state: DownloadState,
+}
+
+impl ResourceMetadata {
+    fn is_busy(&self) -> bool {
+        matches!(
+            self.state,
+            DownloadState::Uploading | DownloadState::Downloading
+        )
+    }
+}
+
+pub(super) struct Storage {
+    dir: PathBuf,
+    resources: Mutex<HashMap<ResourceId, ResourceMetadata>>,
+    // Notified when a resource is inserted or changes state, such that the reaper can
+    // reschedule itself.
+    changed: (channel::Sender<()>, channel::Receiver<()>),
+}
+
+impl Storage {
+    // Open the storage directory, creating it if needed, and load the metadata of the
+    // stored resources. Incomplete uploads are discarded.
+    pub(super) async fn open<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await?;
+
+        let mut resources = HashMap::new();
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let path: PathBuf = entry.path().into();
+
+            let mut metadata = match read_metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    tracing::warn!("Skipping resource at {}: {}", path.display(), error);
+                    continue;
+                }
+            };
+
+            match metadata.state {
+                DownloadState::Uploading => {
+                    fs::remove_dir_all(&path).await?;
+                    continue;
+                }
+                DownloadState::Downloading => metadata.state = DownloadState::Available,
+                DownloadState::Available | DownloadState::Received => {}
+            }
+            resources.insert(metadata.id.clone(), metadata);
+        }
+
+        Ok(Self {
+            dir,
+            resources: Mutex::new(resources),
+            changed: channel::bounded(1),
+        })
+    }
+
+    pub(super) async fn len(&self) -> usize {
+        self.resources.lock().await.len()
+    }
+
+    pub(super) async fn get(&self, id: &ResourceId) -> Option<ResourceMetadata> {
+        self.resources.lock().await.get(id).cloned()
+    }
+
+    // Insert the metadata of a new resource, which is then ready for its blobs to be
+    // uploaded.
+    pub(super) async fn insert(&self, metadata: ResourceMetadata) -> Result<(), Error> {
+        let mut resources = self.resources.lock().await;
+        if resources.contains_key(&metadata.id) {
+            return Err(StorageError::DuplicateResource.into());
+        }
+
+        let path = self.resource_dir(&metadata.id);
+        fs::create_dir_all(&path).await?;
+        write_metadata(&path, &metadata).await?;
+        resources.insert(metadata.id.clone(), metadata);
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    pub(super) async fn create_blob(
+        &self,
+        id: &ResourceId,
+        index: usize,
+    ) -> Result<fs::File, Error> {
+        Ok(fs::File::create(self.resource_dir(id).join(index.to_string())).await?)
+    }
+
+    pub(super) async fn open_blob(&self, id: &ResourceId, index: usize) -> Result<fs::File, Error> {
+        Ok(fs::File::open(self.resource_dir(id).join(index.to_string())).await?)
+    }
+
+    pub(super) async fn complete_upload(
+        &self,
+        id: &ResourceId,
+        digests: Vec<Option<[u8; RESOURCE_DIGEST_LEN]>>,
+    ) -> Result<(), Error> {
+        self.set_state(
+            id,
+            DownloadState::Uploading,
+            DownloadState::Available,
+            |metadata| metadata.digests = digests,
+        )
+        .await
+    }
+
+    // Mark an available resource as being downloaded, such that it cannot be received by
+    // another receiver concurrently.
+    pub(super) async fn begin_download(&self, id: &ResourceId) -> Result<(), Error> {
+        self.set_state(
+            id,
+            DownloadState::Available,
+            DownloadState::Downloading,
+            |_| {},
+        )
+        .await
+    }
+
+    pub(super) async fn abort_download(&self, id: &ResourceId) -> Result<(), Error> {
+        self.set_state(
+            id,
+            DownloadState::Downloading,
+            DownloadState::Available,
+            |_| {},
+        )
+        .await
+    }
+
+    // Mark the resource as received and delete its blobs. The metadata is kept until the
+    // resource is removed.
+    pub(super) async fn complete_download(&self, id: &ResourceId) -> Result<(), Error> {
+        self.set_state(
+            id,
+            DownloadState::Downloading,
+            DownloadState::Received,
+            |_| {},
+        )
+        .await?;
+
+        let count = self
+            .get(id)
+            .await
+            .map_or(0, |metadata| metadata.resources.len());
+        let path = self.resource_dir(id);
+        for index in 0..count {
+            fs::remove_file(path.join(index.to_string())).await.ok();
+        }
+
+        Ok(())
+    }
+
+    // Remove the resource together with its metadata.
+    pub(super) async fn remove(&self, id: &ResourceId) -> Result<(), Error> {
+        let mut resources = self.resources.lock().await;
+        if resources.remove(id).is_none() {
+            return Err(StorageError::ResourceNotFound.into());
+        }
+        fs::remove_dir_all(self.resource_dir(id)).await?;
+
+        Ok(())
+    }
+
+    // Move the resource from one state to another, applying `update` to its metadata in the
+    // same step. The metadata in memory only changes once it has been persisted.
+    async fn set_state(
+        &self,
+        id: &ResourceId,
+        from: DownloadState,
+        to: DownloadState,
+        update: impl FnOnce(&mut ResourceMetadata),
+    ) -> Result<(), Error> {
+        let mut resources = self.resources.lock().await;
+        let metadata = resources
+            .get_mut(id)
+            .ok_or(StorageError::ResourceNotFound)?;
+        if metadata.state != from {
+            return Err(StorageError::InvalidState(metadata.state).into());
+        }
+
+        let mut updated = metadata.clone();
+        updated.state = to;
+        update(&mut updated);
+        if to != DownloadState::Downloading {
+            write_metadata(&self.resource_dir(id), &updated).await?;
+        }
+        *metadata = updated;
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    // The resources that have expired by `now`, excluding those being transferred.
+    pub(super) async fn expired(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<ResourceId> {
+        self.resources
+            .lock()
+            .await
+            .values()
+            .filter(|metadata| !metadata.is_busy() && metadata.expiry <= now)
+            .map(|metadata| metadata.id.clone())
+            .collect()
+    }
+
+    // The earliest expiry time of the resources, excluding those being transferred.
+    pub(super) async fn next_expiry(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.resources
+            .lock()
+            .await
+            .values()
+            .filter(|metadata| !metadata.is_busy())
+            .map(|metadata| metadata.expiry)
+            .min()
+    }
+
+    // Wait until a resource is inserted or changes state.
+    pub(super) async fn changed(&self) {
+        // NOTE: The sender is owned by the storage itself, so the channel is never closed.
+        self.changed.1.recv().await.ok();
+    }
+
+    fn notify_changed(&self) {
+        // NOTE: A full channel already has a pending notification.
+        self.changed.0.try_send(()).ok();
+    }
+
+    fn resource_dir(&self, id: &ResourceId) -> PathBuf {
+        let name =
+            id.0.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>();
+        self.dir.join(name)
+    }
+}
+
+async fn read_metadata(path: &Path) -> Result<ResourceMetadata, Error> {
+    let bytes = fs::read(path.join(METADATA_FILE_NAME)).await?;
+    ciborium::from_reader(bytes.as_slice())
+        .map_err(|err| StorageError::MetadataDeserialization(err.to_string()).into())
+}
+
+// NOTE: The metadata is written to a temporary file first and then renamed, such that a
+// crash never leaves a partially written metadata file behind.
+async fn write_metadata(path: &Path, metadata: &ResourceMetadata) -> Result<(), Error> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(metadata, &mut bytes)
+        .map_err(|err| StorageError::MetadataSerialization(err.to_string()))?;
+
+    let temp_path = path.join(METADATA_TEMP_FILE_NAME);
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(&bytes).await?;
+    file.sync_all().await?;
+    fs::rename(&temp_path, path.join(METADATA_FILE_NAME)).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metadata(id: Vec<u8>) -> ResourceMetadata {
+        ResourceMetadata {
+            id: ResourceId(id),
+            resources: vec![Resource::file(5, "a.txt"), Resource::file(0, "b.txt")],
+            digests: Vec::new(),
+            receiver_control: Some(StoredReceiverControl::PublicKey([0; 32])),
+            expiry: chrono::Utc::now() + chrono::Duration::hours(1),
+            state: DownloadState::Uploading,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_storage_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let available = metadata(vec![1, 2, 3]);
+        let uploading = metadata(vec![4, 5, 6]);
+
+        let storage = Storage::open(dir.path()).await.unwrap();
+        storage.insert(available.clone()).await.unwrap();
+        storage.insert(uploading.clone()).await.unwrap();
+        assert!(matches!(
+            storage.insert(available.clone()).await,
+            Err(Error::Storage(StorageError::DuplicateResource))
+        ));
+
+        let mut blob = storage.create_blob(&available.id, 0).await.unwrap();
+        blob.write_all(b"hello").await.unwrap();
+        blob.flush().await.unwrap();
+        storage.create_blob(&available.id, 1).await.unwrap();
+        let digests = vec![
+            Some([1; RESOURCE_DIGEST_LEN]),
+            Some([2; RESOURCE_DIGEST_LEN]),
+        ];
+        storage
+            .complete_upload(&available.id, digests.clone())
+            .await
+            .unwrap();
+        storage.begin_download(&available.id).await.unwrap();
+        drop(storage);
+
+        // Downloads fall back to available and incomplete uploads are discarded.
+        let storage = Storage::open(dir.path()).await.unwrap();
+        assert_eq!(
+            storage.get(&available.id).await,
+            Some(ResourceMetadata {
+                digests,
+                state: DownloadState::Available,
+                ..available.clone()
+            })
+        );
+        assert!(storage.get(&uploading.id).await.is_none());
+
+        let mut data = String::new();
+        storage
+            .open_blob(&available.id, 0)
+            .await
+            .unwrap()
+            .read_to_string(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, "hello");
+
+        storage.begin_download(&available.id).await.unwrap();
+        assert!(matches!(
+            storage.begin_download(&available.id).await,
+            Err(Error::Storage(StorageError::InvalidState(
+                DownloadState::Downloading
+            )))
+        ));
+        storage.complete_download(&available.id).await.unwrap();
+        assert!(storage.open_blob(&available.id, 0).await.is_err());
+        drop(storage);
+
+        let storage = Storage::open(dir.path()).await.unwrap();
+        assert_eq!(
+            storage.get(&available.id).await.unwrap().state,
+            DownloadState::Received
+        );
+        storage.remove(&available.id).await.unwrap();
+        assert!(storage.get(&available.id).await.is_none());
+    }
+
+    #[async_std::test]
+    async fn test_failed_write_keeps_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let uploading = metadata(vec![1, 2, 3]);
+
+        let storage = Storage::open(dir.path()).await.unwrap();
+        storage.insert(uploading.clone()).await.unwrap();
+        fs::remove_dir_all(storage.resource_dir(&uploading.id))
+            .await
+            .unwrap();
+
+        // Neither the digests nor the state change in memory when persisting them fails.
+        assert!(storage
+            .complete_upload(&uploading.id, vec![None, None])
+            .await
+            .is_err());
+        assert_eq!(storage.get(&uploading.id).await, Some(uploading));
+    }
+}
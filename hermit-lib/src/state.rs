@@ -0,0 +1,13 @@
+use crate::proto::stream::{Plain, Secure};
+
+pub trait State {}
+pub trait PlainState: State {
+    type PlainStream: Plain;
+    fn plain_stream(&mut self) -> &mut Self::PlainStream;
+}
+pub trait SecureState: PlainState {
+    type DowngradeState: PlainState;
+    type SecureStream: Secure;
+    fn secure_stream(&mut self) -> &mut Self::SecureStream;
+    fn downgrade(self) -> Self::DowngradeState;
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+use async_std::net::ToSocketAddrs;
+use clap::Args;
+use hermit_lib::client::{Client, ServerSigPubKey, UpgradedConnection};
+use hermit_lib::crypto::{identity, CipherSuite};
+use hermit_lib::error::{CryptoError, Error};
+use hermit_lib::proto::stream::BaseStream;
+
+use crate::key;
+use crate::known_servers::KnownServers;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transport {
+    Tcp,
+    Quic,
+}
+
+// A server address in the form `[tcp://|quic://]host:port`, defaulting to TCP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServerAddr {
+    pub(crate) transport: Transport,
+    pub(crate) host: String,
+}
+
+impl FromStr for ServerAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (transport, host) = match s.split_once("://") {
+            Some(("tcp", host)) => (Transport::Tcp, host),
+            Some(("quic", host)) => (Transport::Quic, host),
+            Some((scheme, _)) => anyhow::bail!("unsupported transport: {}", scheme),
+            None => (Transport::Tcp, s),
+        };
+        if host.rsplit_once(':').is_none() {
+            anyhow::bail!("server address must include a port: {}", host);
+        }
+
+        Ok(Self {
+            transport,
+            host: host.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for ServerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.transport {
+            Transport::Tcp => write!(f, "tcp://{}", self.host),
+            Transport::Quic => write!(f, "quic://{}", self.host),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ServerArgs {
+    /// Server address, as `[tcp://|quic://]host:port`
+    pub(crate) server: ServerAddr,
+    /// Server's signature public key (base64 or niceware), instead of the known servers
+    #[arg(long)]
+    pub(crate) server_key: Option<String>,
+    /// Known servers file [default: <config dir>/hermit/known_servers]
+    #[arg(long)]
+    pub(crate) known_servers: Option<PathBuf>,
+    /// Trust and record the key presented by a server not yet known
+    #[arg(long)]
+    pub(crate) trust_on_first_use: bool,
+    /// Cipher suite to offer, in order of preference (repeatable): aes-128-gcm, aes-256-gcm or
+    /// chacha20-poly1305 [default: all]
+    #[arg(long = "cipher-suite", value_name = "SUITE")]
+    pub(crate) cipher_suites: Vec<CipherSuite>,
+    /// Key file to authenticate to the server with, if it only serves known clients
+    #[arg(long)]
+    pub(crate) client_key: Option<PathBuf>,
+}
+
+impl ServerArgs {
+    // Connect to the server and perform the handshake, verifying the server with the given
+    // key or the one recorded in the known servers.
+    pub(crate) async fn connect(&self) -> anyhow::Result<Client<UpgradedConnection>> {
+        let client_key_pair = match &self.client_key {
+            Some(path) => Some(
+                identity::load_identity_key(path)
+                    .await
+                    .with_context(|| format!("cannot load client key from {}", path.display()))?,
+            ),
+            None => None,
+        };
+        let known_servers_path = match &self.known_servers {
+            Some(path) => path.clone(),
+            None => KnownServers::default_path().context("no config directory found")?,
+        };
+        let mut known_servers = KnownServers::open(&known_servers_path).await?;
+        let known_sig_pub_key = known_servers.get(&self.server.host);
+        let sig_pub_key = match &self.server_key {
+            Some(server_key) => Some(key::decode_public_key(server_key)?),
+            None => known_sig_pub_key,
+        };
+        if sig_pub_key.is_none() && !self.trust_on_first_use {
+            anyhow::bail!(
+                "unknown server {}; pass its key with --server-key, or --trust-on-first-use to \
+                 record the key it presents",
+                self.server.host
+            );
+        }
+
+        let addr = resolve(&self.server.host).await?;
+        let stream = match self.server.transport {
+            Transport::Tcp => BaseStream::connect_tcp(addr).await,
+            Transport::Quic => BaseStream::connect_quic(addr).await,
+        }
+        .with_context(|| format!("failed to connect to {}", self.server))?;
+
+        let mut client = Client::new().connect(stream);
+        if !self.cipher_suites.is_empty() {
+            client.set_cipher_suites(&self.cipher_suites);
+        }
+        let (client, sig_pub_key) = match sig_pub_key {
+            Some(sig_pub_key) => client
+                .handshake(ServerSigPubKey::from(sig_pub_key))
+                .await
+                .map(|client| (client, sig_pub_key)),
+            None => client.handshake_trust_on_first_use().await,
+        }
+        .map_err(|(_, error)| match error {
+            Error::Crypto(CryptoError::ServerSigPubKeyMismatch) => anyhow::anyhow!(
+                "THE IDENTITY OF SERVER {} HAS CHANGED!\n\
+                 It presented a different key than {}, which means someone may be \
+                 impersonating it. Only if the change is expected, update the key.",
+                self.server.host,
+                match self.server_key {
+                    Some(_) => "the one given with --server-key".to_owned(),
+                    None => format!("the one recorded in {}", known_servers.path().display()),
+                }
+            ),
+            error => {
+                anyhow::Error::new(error).context(format!("handshake with {} failed", self.server))
+            }
+        })?;
+
+        if known_sig_pub_key.is_none() {
+            known_servers.insert(&self.server.host, sig_pub_key).await?;
+            eprintln!(
+                "Recorded server {} with key {} in {}",
+                self.server.host,
+                key::encode_public_key(&sig_pub_key),
+                known_servers.path().display()
+            );
+        }
+
+        match client_key_pair {
+            Some(client_key_pair) => {
+                client
+                    .authenticate(&client_key_pair)
+                    .await
+                    .map_err(|(_, error)| {
+                        anyhow::Error::new(error)
+                            .context(format!("authentication to {} failed", self.server))
+                    })
+            }
+            None => Ok(client),
+        }
+    }
+}
+
+async fn resolve(host: &str) -> anyhow::Result<SocketAddr> {
+    host.to_socket_addrs()
+        .await
+        .with_context(|| format!("failed to resolve {}", host))?
+        .next()
+        .with_context(|| format!("no address found for {}", host))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_addr() {
+        let addr = "example.com:4000".parse::<ServerAddr>().unwrap();
+        assert_eq!(addr.transport, Transport::Tcp);
+        assert_eq!(addr.host, "example.com:4000");
+
+        let addr = "quic://[::1]:4000".parse::<ServerAddr>().unwrap();
+        assert_eq!(addr.transport, Transport::Quic);
+        assert_eq!(addr.host, "[::1]:4000");
+        assert_eq!(addr.to_string(), "quic://[::1]:4000");
+
+        assert!("http://example.com:80".parse::<ServerAddr>().is_err());
+        assert!("example.com".parse::<ServerAddr>().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use base64::Engine;
+use clap::{Args, Subcommand};
+use hermit_lib::crypto::identity;
+
+const ED25519_PUBLIC_KEY_LEN: usize = 32;
+const NICEWARE_SEPARATOR: char = '-';
+
+#[derive(Args, Debug)]
+pub(crate) struct KeygenArgs {
+    /// File to write the new key to, which must not exist yet
+    path: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum KeyCommand {
+    /// Show the public key of a key file
+    Show {
+        /// Key file
+        path: PathBuf,
+    },
+}
+
+// Generate a key for a server identity, or for a receiver of resources locked with its
+// public key.
+pub(crate) async fn keygen(args: KeygenArgs) -> anyhow::Result<()> {
+    let key_pair = identity::generate_identity_key(&args.path)
+        .await
+        .with_context(|| format!("cannot generate key {}", args.path.display()))?;
+
+    println!("Generated key at {}", args.path.display());
+    print_public_key(&identity::export_public_key(&key_pair))
+}
+
+pub(crate) async fn key(command: KeyCommand) -> anyhow::Result<()> {
+    match command {
+        KeyCommand::Show { path } => {
+            let key_pair = identity::load_identity_key(&path)
+                .await
+                .with_context(|| format!("cannot load key {}", path.display()))?;
+            print_public_key(&identity::export_public_key(&key_pair))
+        }
+    }
+}
+
+pub(crate) fn print_public_key(public_key: &[u8]) -> anyhow::Result<()> {
+    println!("Public key (base64):   {}", encode_public_key(public_key));
+    println!(
+        "Public key (niceware): {}",
+        encode_public_key_words(public_key)?
+    );
+    Ok(())
+}
+
+// Public keys are exchanged as standard base64 or niceware words on the command line.
+// NOTE: The base64 alphabet contains neither the separator nor whitespace, so the two forms
+// cannot be confused.
+pub(crate) fn decode_public_key(encoded: &str) -> anyhow::Result<[u8; ED25519_PUBLIC_KEY_LEN]> {
+    let encoded = encoded.trim();
+    let bytes = if encoded.contains(|c: char| c == NICEWARE_SEPARATOR || c.is_whitespace()) {
+        let words = encoded
+            .split(|c: char| c == NICEWARE_SEPARATOR || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        niceware::passphrase_to_bytes(&words)
+            .map_err(|error| anyhow::anyhow!("public key is not valid niceware: {}", error))?
+    } else {
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .context("public key is not valid base64")?
+    };
+
+    <[u8; ED25519_PUBLIC_KEY_LEN]>::try_from(bytes.as_slice()).map_err(|_| {
+        anyhow::anyhow!(
+            "public key must be {} bytes, got {}",
+            ED25519_PUBLIC_KEY_LEN,
+            bytes.len()
+        )
+    })
+}
+
+pub(crate) fn encode_public_key(public_key: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(public_key)
+}
+
+pub(crate) fn encode_public_key_words(public_key: &[u8]) -> anyhow::Result<String> {
+    let words = niceware::bytes_to_passphrase(public_key)
+        .map_err(|error| anyhow::anyhow!("cannot encode public key: {}", error))?;
+    Ok(words.join(&NICEWARE_SEPARATOR.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_public_key() {
+        let public_key = [7u8; ED25519_PUBLIC_KEY_LEN];
+        let encoded = encode_public_key(&public_key);
+        assert_eq!(decode_public_key(&encoded).unwrap(), public_key);
+        assert_eq!(
+            decode_public_key(&format!(" {}\n", encoded)).unwrap(),
+            public_key
+        );
+
+        let words = encode_public_key_words(&public_key).unwrap();
+        assert_eq!(words.split(NICEWARE_SEPARATOR).count(), 16);
+        assert_eq!(decode_public_key(&words).unwrap(), public_key);
+        assert_eq!(
+            decode_public_key(&words.replace(NICEWARE_SEPARATOR, " ").to_uppercase()).unwrap(),
+            public_key
+        );
+
+        assert!(decode_public_key("not base64!").is_err());
+        assert!(decode_public_key("not-niceware").is_err());
+        assert!(decode_public_key(&encode_public_key(&[7u8; 16])).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_std::fs;
+use async_std::prelude::*;
+
+use crate::key;
+
+const KNOWN_SERVERS_FILE_NAME: &str = "known_servers";
+
+// FORMAT: Like `known_hosts`, each line maps a server to its signature public key:
+//   host:port <base64 public key>
+// Blank lines and lines starting with `#` are ignored.
+pub(crate) struct KnownServers {
+    path: PathBuf,
+    servers: BTreeMap<String, [u8; 32]>,
+}
+
+impl KnownServers {
+    // The default location is `hermit/known_servers` in the user's config directory.
+    pub(crate) fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("hermit").join(KNOWN_SERVERS_FILE_NAME))
+    }
+
+    // Open the known servers file, which is treated as empty if it does not exist yet.
+    pub(crate) async fn open(path: &Path) -> anyhow::Result<Self> {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("cannot read known servers {}", path.display()))
+            }
+        };
+        let servers =
+            parse(&content).with_context(|| format!("invalid known servers {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            servers,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn get(&self, server: &str) -> Option<[u8; 32]> {
+        self.servers.get(&server.to_lowercase()).copied()
+    }
+
+    // Record a new server by appending it to the file, keeping any existing comments.
+    pub(crate) async fn insert(
+        &mut self,
+        server: &str,
+        public_key: [u8; 32],
+    ) -> anyhow::Result<()> {
+        let server = server.to_lowercase();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("cannot write known servers {}", self.path.display()))?;
+        file.write_all(format!("{} {}\n", server, key::encode_public_key(&public_key)).as_bytes())
+            .await?;
+        file.sync_all().await?;
+
+        self.servers.insert(server, public_key);
+        Ok(())
+    }
+}
+
+fn parse(content: &str) -> anyhow::Result<BTreeMap<String, [u8; 32]>> {
+    let mut servers = BTreeMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (server, public_key) = line
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("line {}: expected `host:port <public key>`", index + 1))?;
+        let public_key = key::decode_public_key(public_key)
+            .with_context(|| format!("line {}: invalid public key", index + 1))?;
+        servers.insert(server.to_lowercase(), public_key);
+    }
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_known_servers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(KNOWN_SERVERS_FILE_NAME);
+        std::fs::write(
+            &path,
+            format!(
+                "# known servers\n\nexample.com:4040 {}\n",
+                key::encode_public_key(&[1; 32])
+            ),
+        )
+        .unwrap();
+
+        let mut known_servers = KnownServers::open(&path).await.unwrap();
+        assert_eq!(known_servers.get("EXAMPLE.com:4040"), Some([1; 32]));
+        assert_eq!(known_servers.get("example.com:4041"), None);
+        known_servers.insert("[::1]:4040", [2; 32]).await.unwrap();
+
+        let known_servers = KnownServers::open(&path).await.unwrap();
+        assert_eq!(known_servers.get("example.com:4040"), Some([1; 32]));
+        assert_eq!(known_servers.get("[::1]:4040"), Some([2; 32]));
+
+        std::fs::write(&path, "example.com:4040\n").unwrap();
+        assert!(KnownServers::open(&path).await.is_err());
+        assert!(KnownServers::open(&dir.path().join("missing"))
+            .await
+            .is_ok());
+    }
+}
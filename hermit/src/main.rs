@@ -1,22 +1,49 @@
-use async_std::io;
-use async_std::net::TcpStream;
-use async_std::prelude::*;
+mod connect;
+mod key;
+mod known_servers;
+mod partial;
+mod progress;
+mod receive;
+mod send;
+mod serve;
+
 use async_std::task;
+use clap::{Parser, Subcommand};
 
-fn main() -> io::Result<()> {
-    task::block_on(async {
-        let mut stream = TcpStream::connect("127.0.0.1:8080").await?;
-        println!("Connected to {}", &stream.peer_addr()?);
-        println!("Connected to {}", &stream.local_addr()?);
+#[derive(Parser, Debug)]
+#[command(version, about = "Simple, lightweight and secure file transfer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-        let msg = "hello world";
-        println!("<- {}", msg);
-        stream.write_all(msg.as_bytes()).await?;
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send files to a server
+    Send(send::SendArgs),
+    /// Receive files from a server
+    Receive(receive::ReceiveArgs),
+    /// Run a server
+    Serve(serve::ServeArgs),
+    /// Generate a key for a server identity or a receiver
+    Keygen(key::KeygenArgs),
+    /// Manage key files
+    Key {
+        #[command(subcommand)]
+        command: key::KeyCommand,
+    },
+}
 
-        let mut buf = vec![0u8; 1024];
-        let n = stream.read(&mut buf).await?;
-        println!("-> {}\n", String::from_utf8_lossy(&buf[..n]));
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
 
-        Ok(())
+    task::block_on(async {
+        match cli.command {
+            Command::Send(args) => send::send(args).await,
+            Command::Receive(args) => receive::receive(args).await,
+            Command::Serve(args) => serve::serve(args).await,
+            Command::Keygen(args) => key::keygen(args).await,
+            Command::Key { command } => key::key(command).await,
+        }
     })
 }
@@ -0,0 +1,97 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_std::fs;
+use hermit_lib::proto::message::transfer::{Resource, ResourceId};
+use serde::{Deserialize, Serialize};
+
+// A download in progress, recorded in the output directory such that it can be resumed
+// after an interruption. It lists the manifest of the resource, whose files on disk are
+// then known to be partial downloads rather than unrelated files.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct PartialDownload {
+    pub(crate) resources: Vec<Resource>,
+}
+
+impl PartialDownload {
+    // FORMAT: TOML, in the hidden file `.hermit-<resource ID>.partial`.
+    pub(crate) fn path(output: &Path, id: &ResourceId) -> PathBuf {
+        output.join(format!(".hermit-{}.partial", id))
+    }
+
+    // Load the partial download recorded at the path, if any.
+    pub(crate) async fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("cannot read partial download {}", path.display()))
+            }
+        };
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("invalid partial download {}", path.display()))
+    }
+
+    pub(crate) async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, toml::to_string(self)?)
+            .await
+            .with_context(|| format!("cannot write partial download {}", path.display()))
+    }
+
+    // The offset from which to resume each resource, i.e. the length of what is already on
+    // disk. A file longer than its declared size is received again from the start.
+    pub(crate) async fn offsets(&self, output: &Path) -> Vec<u64> {
+        let mut offsets = Vec::with_capacity(self.resources.len());
+        for resource in &self.resources {
+            let len = match resource.is_dir() {
+                true => None,
+                false => fs::metadata(resource.local_path(output))
+                    .await
+                    .ok()
+                    .map(|metadata| metadata.len()),
+            };
+            offsets.push(len.filter(|len| *len <= resource.size).unwrap_or(0));
+        }
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_partial_download() {
+        let output = tempfile::tempdir().unwrap();
+        let id = "a-zyzzyva".parse::<ResourceId>().unwrap();
+        let path = PartialDownload::path(output.path(), &id);
+        assert_eq!(path, output.path().join(".hermit-a-zyzzyva.partial"));
+        assert_eq!(PartialDownload::load(&path).await.unwrap(), None);
+
+        let partial = PartialDownload {
+            resources: vec![
+                Resource::file(5, "a.txt"),
+                Resource::directory("dir"),
+                Resource::file(3, "dir/b.txt"),
+                Resource::file(3, "dir/c.txt"),
+            ],
+        };
+        partial.save(&path).await.unwrap();
+        assert_eq!(
+            PartialDownload::load(&path).await.unwrap().as_ref(),
+            Some(&partial)
+        );
+
+        fs::write(output.path().join("a.txt"), b"hel")
+            .await
+            .unwrap();
+        fs::create_dir(output.path().join("dir")).await.unwrap();
+        fs::write(output.path().join("dir").join("b.txt"), b"abcd")
+            .await
+            .unwrap();
+        assert_eq!(partial.offsets(output.path()).await, vec![3, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use hermit_lib::client::Progress;
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+
+// A progress bar on stderr over all resources of a request, hidden when stderr is not a
+// terminal.
+pub(crate) fn progress_bar(total_size: u64) -> ProgressBar {
+    ProgressBar::new(total_size).with_style(
+        ProgressStyle::with_template("{prefix} [{bar:30}] {bytes}/{total_bytes} {msg}")
+            // SAFETY: the template is valid
+            .unwrap()
+            .progress_chars("=> "),
+    )
+}
+
+// An observer of the client's progress that updates the given bar.
+pub(crate) fn observer(bar: &ProgressBar) -> impl FnMut(&Progress) + Send + 'static {
+    let bar = bar.clone();
+    move |progress| {
+        bar.set_prefix(progress.name.clone());
+        bar.set_position(progress.total_transferred);
+        bar.set_message(rate(progress));
+    }
+}
+
+fn rate(progress: &Progress) -> String {
+    if progress.throughput <= 0.0 {
+        return String::new();
+    }
+
+    let remaining = progress
+        .total_size
+        .saturating_sub(progress.total_transferred);
+    let eta = Duration::from_secs_f64(remaining as f64 / progress.throughput);
+    format!(
+        "{}/s, ETA {}",
+        HumanBytes(progress.throughput as u64),
+        HumanDuration(eta)
+    )
+}
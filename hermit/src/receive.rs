@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use async_std::fs;
+use clap::Args;
+use hermit_lib::crypto::identity;
+use hermit_lib::error::{Error, ResourceRequestError, TransferError};
+use hermit_lib::proto::message::transfer::{
+    Compression, ReceiveResourceRequest, ReceiverControl, ResourceId, ResourceKind,
+};
+
+use crate::connect::ServerArgs;
+use crate::partial::PartialDownload;
+use crate::progress;
+
+#[derive(Args, Debug)]
+pub(crate) struct ReceiveArgs {
+    #[command(flatten)]
+    server: ServerArgs,
+    /// Resource ID given to the sender
+    resource_id: ResourceId,
+    /// Directory to write the received files to
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+    /// Password set by the sender
+    #[arg(long, conflicts_with = "key")]
+    password: Option<String>,
+    /// Receiver key file, whose public key was given to the sender
+    #[arg(long)]
+    key: Option<PathBuf>,
+    /// Receive the files uncompressed, e.g. if they are already compressed
+    #[arg(long)]
+    no_compression: bool,
+}
+
+pub(crate) async fn receive(args: ReceiveArgs) -> anyhow::Result<()> {
+    let key_pair = match &args.key {
+        Some(path) => Some(
+            identity::load_identity_key(path)
+                .await
+                .with_context(|| format!("cannot load key from {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let control = match (args.password, &key_pair) {
+        (Some(password), _) => Some(ReceiverControl::Password(password)),
+        (_, Some(key_pair)) => Some(ReceiverControl::PublicKey(identity::export_public_key(
+            key_pair,
+        ))),
+        (None, None) => None,
+    };
+
+    // NOTE: A partial download left behind by an earlier attempt is resumed from what is
+    // already on disk.
+    let partial_path = PartialDownload::path(&args.output, &args.resource_id);
+    let partial = PartialDownload::load(&partial_path).await?;
+    let offsets = match &partial {
+        Some(partial) => partial.offsets(&args.output).await,
+        None => Vec::new(),
+    };
+
+    let client = args.server.connect().await?;
+    let mut client = match client
+        .receive_resource_request(
+            ReceiveResourceRequest {
+                id: args.resource_id.clone(),
+                control,
+                offsets: offsets.clone(),
+                compression: match args.no_compression {
+                    true => Vec::new(),
+                    false => Compression::SUPPORTED.to_vec(),
+                },
+            },
+            key_pair.as_ref(),
+        )
+        .await
+    {
+        Ok(client) => client,
+        Err((_, Error::ResourceRequest(ResourceRequestError::Failed))) => anyhow::bail!(
+            "resource {} cannot be received; it may not exist, have expired, have already \
+             been received, or the password or key may be wrong",
+            args.resource_id
+        ),
+        Err((_, error)) => return Err(error).context("receive request rejected"),
+    };
+
+    println!("Resource {}:", args.resource_id);
+    for resource in client.resources() {
+        match resource.kind {
+            ResourceKind::File => println!("  {:>12}  {}", resource.size, resource.path),
+            ResourceKind::Directory => println!("  {:>12}  {}/", "", resource.path),
+        }
+    }
+
+    // NOTE: The manifest has been validated by the client, such that every path is under
+    // the output directory.
+    let resources = client.resources().to_vec();
+    match &partial {
+        Some(partial) if partial.resources != resources => anyhow::bail!(
+            "resource {} does not match the partial download recorded in {}",
+            args.resource_id,
+            partial_path.display()
+        ),
+        Some(_) => eprintln!("Resuming the partial download"),
+        None => {
+            // NOTE: Existing files are never overwritten by a new download, so any conflict
+            // is reported before the download is recorded.
+            for resource in resources.iter().filter(|resource| !resource.is_dir()) {
+                let path = resource.local_path(&args.output);
+                if fs::symlink_metadata(&path).await.is_ok() {
+                    anyhow::bail!("{} already exists", path.display());
+                }
+            }
+            fs::create_dir_all(&args.output)
+                .await
+                .with_context(|| format!("cannot create {}", args.output.display()))?;
+            PartialDownload {
+                resources: resources.clone(),
+            }
+            .save(&partial_path)
+            .await?;
+        }
+    }
+
+    let bar = progress::progress_bar(resources.iter().map(|resource| resource.size).sum());
+    client.set_progress_observer(progress::observer(&bar));
+    let mut file_count = 0;
+    for (index, resource) in resources.iter().enumerate() {
+        let path = resource.local_path(&args.output);
+        if resource.is_dir() {
+            fs::create_dir_all(&path)
+                .await
+                .with_context(|| format!("cannot create {}", path.display()))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("cannot create {}", parent.display()))?;
+        }
+
+        // NOTE: The files of a recorded download are appended to from the offset, or
+        // truncated if received from the start.
+        let offset = offsets.get(index).copied().unwrap_or(0);
+        let mut options = fs::OpenOptions::new();
+        match (&partial, offset) {
+            (None, _) => options.write(true).create_new(true),
+            (Some(_), 0) => options.write(true).create(true).truncate(true),
+            (Some(_), _) => options.append(true),
+        };
+        let file = options
+            .open(&path)
+            .await
+            .with_context(|| format!("cannot create {}", path.display()))?;
+        let result = match offset {
+            0 => client.receive_resource(file).await,
+            _ => {
+                let partial_file = fs::File::open(&path)
+                    .await
+                    .with_context(|| format!("cannot read {}", path.display()))?;
+                client.resume_resource(partial_file, file).await
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(Error::Transfer(TransferError::DigestMismatch)) => {
+                bar.abandon();
+                // NOTE: The file is removed such that the next attempt receives it from the
+                // start rather than resuming corrupted data.
+                fs::remove_file(&path)
+                    .await
+                    .with_context(|| format!("cannot remove {}", path.display()))?;
+                anyhow::bail!(
+                    "{} is corrupted and has been removed; run the same command again to \
+                     receive it from the start",
+                    path.display()
+                );
+            }
+            Err(error) => {
+                bar.abandon();
+                return Err(error).with_context(|| {
+                    format!(
+                        "failed to receive {}; run the same command again to resume",
+                        path.display()
+                    )
+                });
+            }
+        }
+        file_count += 1;
+    }
+    bar.finish_and_clear();
+
+    client
+        .receive_completed()
+        .await
+        .map_err(|(_, error)| error)
+        .context("failed to acknowledge the download")?
+        .disconnect()
+        .await?;
+    fs::remove_file(&partial_path)
+        .await
+        .with_context(|| format!("cannot remove {}", partial_path.display()))?;
+
+    println!(
+        "Received {} file(s) into {}",
+        file_count,
+        args.output.display()
+    );
+
+    Ok(())
+}
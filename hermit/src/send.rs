@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use async_std::fs;
+use clap::Args;
+use hermit_lib::client;
+use hermit_lib::proto::message::transfer::{Compression, ReceiverControl, SendResourceRequest};
+
+use crate::connect::ServerArgs;
+use crate::{key, progress};
+
+#[derive(Args, Debug)]
+pub(crate) struct SendArgs {
+    #[command(flatten)]
+    server: ServerArgs,
+    /// Files or directories to send
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Suggested expiry duration, e.g. `30min`, `1d` (the server may adjust it)
+    #[arg(long, value_parser = parse_expiry)]
+    expiry: Option<chrono::Duration>,
+    /// Password required by the receiver
+    #[arg(long, conflicts_with = "recipient_key")]
+    password: Option<String>,
+    /// Signature public key of the receiver (base64 or niceware)
+    #[arg(long)]
+    recipient_key: Option<String>,
+    /// Send the files uncompressed, e.g. if they are already compressed
+    #[arg(long)]
+    no_compression: bool,
+}
+
+fn parse_expiry(s: &str) -> anyhow::Result<chrono::Duration> {
+    let duration = humantime::parse_duration(s)?;
+    Ok(chrono::Duration::from_std(duration)?)
+}
+
+pub(crate) async fn send(args: SendArgs) -> anyhow::Result<()> {
+    let receiver_control = match (args.password, args.recipient_key) {
+        (Some(password), _) => Some(ReceiverControl::Password(password)),
+        (_, Some(recipient_key)) => Some(ReceiverControl::PublicKey(key::decode_public_key(
+            &recipient_key,
+        )?)),
+        (None, None) => None,
+    };
+
+    let mut manifest = Vec::new();
+    for path in &args.paths {
+        manifest.extend(
+            client::local_resources(path)
+                .await
+                .with_context(|| format!("cannot read {}", path.display()))?,
+        );
+    }
+    // NOTE: Directories carry no data, so only the files are opened and sent.
+    let files = manifest
+        .iter()
+        .filter(|(resource, _)| !resource.is_dir())
+        .map(|(_, local_path)| local_path.clone())
+        .collect::<Vec<_>>();
+    let resources = manifest
+        .into_iter()
+        .map(|(resource, _)| resource)
+        .collect::<Vec<_>>();
+    let total_size = resources.iter().map(|resource| resource.size).sum();
+
+    let client = args.server.connect().await?;
+    let mut client = client
+        .send_resource_request(SendResourceRequest {
+            resources,
+            expiry_duration: args.expiry,
+            receiver_control,
+            compression: match args.no_compression {
+                true => Vec::new(),
+                false => Compression::SUPPORTED.to_vec(),
+            },
+        })
+        .await
+        .map_err(|(_, error)| error)
+        .context("send request rejected")?;
+
+    let bar = progress::progress_bar(total_size);
+    client.set_progress_observer(progress::observer(&bar));
+    for path in &files {
+        let file = fs::File::open(path)
+            .await
+            .with_context(|| format!("cannot open {}", path.display()))?;
+        client
+            .send_resource(file)
+            .await
+            .with_context(|| format!("failed to send {}", path.display()))?;
+    }
+    bar.finish_and_clear();
+    let id = client.resource_id().clone();
+    let expiry = client.expiry();
+
+    client
+        .send_completed()
+        .await
+        .map_err(|(_, error)| error)
+        .context("server did not acknowledge the upload")?
+        .disconnect()
+        .await?;
+
+    println!("Resource ID: {}", id);
+    println!(
+        "Expires at:  {}",
+        expiry
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S %:z")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use async_std::fs;
+use clap::Args;
+use hermit_lib::crypto::secrets::KeyUpdateLimits;
+use hermit_lib::crypto::{identity, CipherSuite};
+use hermit_lib::server::{Config, Listener};
+use serde::Deserialize;
+
+use crate::key;
+
+#[derive(Args, Debug)]
+pub(crate) struct ServeArgs {
+    /// Server configuration file (TOML)
+    #[arg(long)]
+    config: PathBuf,
+}
+
+// NOTE: The optional settings fall back to the defaults of `hermit_lib::server::Config`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ServeConfig {
+    tcp_listen: SocketAddr,
+    quic_listen: SocketAddr,
+    identity_key: PathBuf,
+    storage_dir: PathBuf,
+    max_resource_size: Option<u64>,
+    #[serde(default, with = "humantime_serde")]
+    default_expiry: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    min_expiry: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    max_expiry: Option<Duration>,
+    max_len_limit: Option<usize>,
+    cipher_suites: Option<Vec<String>>,
+    key_update_frames: Option<u64>,
+    key_update_bytes: Option<u64>,
+    allowed_clients: Option<Vec<String>>,
+}
+
+impl ServeConfig {
+    async fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("cannot read config {}", path.display()))?;
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("invalid config {}", path.display()))?;
+
+        // Relative paths are resolved against the directory of the config file.
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.identity_key = base.join(&config.identity_key);
+        config.storage_dir = base.join(&config.storage_dir);
+
+        Ok(config)
+    }
+
+    fn server_config(&self) -> anyhow::Result<Config> {
+        let mut config = Config::new(&self.storage_dir);
+        let duration = |duration: Duration| {
+            chrono::Duration::from_std(duration).context("expiry duration too large")
+        };
+
+        if let Some(max_resource_size) = self.max_resource_size {
+            config.max_resource_size = max_resource_size;
+        }
+        if let Some(default_expiry) = self.default_expiry {
+            config.expiry_duration = duration(default_expiry)?;
+        }
+        if let Some(min_expiry) = self.min_expiry {
+            config.min_expiry_duration = duration(min_expiry)?;
+        }
+        if let Some(max_expiry) = self.max_expiry {
+            config.max_expiry_duration = duration(max_expiry)?;
+        }
+        if let Some(max_len_limit) = self.max_len_limit {
+            config.max_len_limit = max_len_limit;
+        }
+        if let Some(cipher_suites) = &self.cipher_suites {
+            config.cipher_suites = cipher_suites
+                .iter()
+                .map(|suite| suite.parse::<CipherSuite>())
+                .collect::<Result<_, _>>()?;
+            if config.cipher_suites.is_empty() {
+                anyhow::bail!("at least one cipher suite must be allowed");
+            }
+        }
+        if self.key_update_frames.is_some() || self.key_update_bytes.is_some() {
+            let limits = config.key_update_limits;
+            config.key_update_limits = KeyUpdateLimits::new(
+                self.key_update_frames.unwrap_or(limits.frames()),
+                self.key_update_bytes.unwrap_or(limits.bytes()),
+            )
+            .context("invalid key_update_frames or key_update_bytes")?;
+        }
+        if let Some(allowed_clients) = &self.allowed_clients {
+            config.allowed_clients = Some(
+                allowed_clients
+                    .iter()
+                    .map(|client_key| key::decode_public_key(client_key))
+                    .collect::<Result<_, _>>()
+                    .context("invalid key in allowed_clients")?,
+            );
+        }
+
+        if config.min_expiry_duration <= chrono::Duration::zero()
+            || config.min_expiry_duration > config.expiry_duration
+            || config.expiry_duration > config.max_expiry_duration
+        {
+            anyhow::bail!(
+                "expiry durations must satisfy 0 < min_expiry <= default_expiry <= max_expiry"
+            );
+        }
+
+        Ok(config)
+    }
+}
+
+pub(crate) async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let serve_config = ServeConfig::read(&args.config).await?;
+    let config = serve_config.server_config()?;
+
+    let key_path = &serve_config.identity_key;
+    let sig_key_pair = if fs::metadata(key_path).await.is_ok() {
+        identity::load_identity_key(key_path)
+            .await
+            .with_context(|| format!("cannot load identity key {}", key_path.display()))?
+    } else {
+        let sig_key_pair = identity::generate_identity_key(key_path)
+            .await
+            .with_context(|| format!("cannot generate identity key {}", key_path.display()))?;
+        println!("Generated identity key at {}", key_path.display());
+        sig_key_pair
+    };
+
+    let listener = Listener::bind(serve_config.tcp_listen, serve_config.quic_listen)
+        .await
+        .context("cannot bind listen addresses")?;
+
+    key::print_public_key(&identity::export_public_key(&sig_key_pair))?;
+    println!("Listening on tcp://{}", listener.tcp_local_addr()?);
+    println!("Listening on quic://{}", listener.quic_local_addr()?);
+
+    listener.listen(sig_key_pair, config).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_example_config() {
+        let config: ServeConfig = toml::from_str(include_str!("../server.example.toml")).unwrap();
+        let server_config = config.server_config().unwrap();
+        assert_eq!(server_config.max_resource_size, 1 << 30);
+        assert_eq!(server_config.expiry_duration, chrono::Duration::days(1));
+        assert_eq!(
+            server_config.min_expiry_duration,
+            chrono::Duration::minutes(1)
+        );
+        assert_eq!(
+            server_config.max_expiry_duration,
+            chrono::Duration::weeks(1)
+        );
+        assert_eq!(server_config.max_len_limit, 32767);
+        assert_eq!(server_config.cipher_suites, CipherSuite::SUPPORTED);
+        assert_eq!(server_config.key_update_limits.frames(), 1 << 24);
+        assert_eq!(server_config.key_update_limits.bytes(), 1 << 34);
+        assert_eq!(server_config.allowed_clients, None);
+
+        let config: ServeConfig = toml::from_str(
+            r#"
+            tcp_listen = "127.0.0.1:4040"
+            quic_listen = "127.0.0.1:4040"
+            identity_key = "identity.key"
+            storage_dir = "storage"
+            min_expiry = "2days"
+            "#,
+        )
+        .unwrap();
+        assert!(config.server_config().is_err());
+
+        let config: ServeConfig = toml::from_str(
+            r#"
+            tcp_listen = "127.0.0.1:4040"
+            quic_listen = "127.0.0.1:4040"
+            identity_key = "identity.key"
+            storage_dir = "storage"
+            cipher_suites = ["aes-128-gcm", "aes-512-gcm"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.server_config().is_err());
+
+        let config: ServeConfig = toml::from_str(
+            r#"
+            tcp_listen = "127.0.0.1:4040"
+            quic_listen = "127.0.0.1:4040"
+            identity_key = "identity.key"
+            storage_dir = "storage"
+            allowed_clients = ["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=", "not a key"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.server_config().is_err());
+
+        for key_update_limit in ["key_update_frames = 0", "key_update_bytes = 0"] {
+            let config: ServeConfig = toml::from_str(&format!(
+                r#"
+                tcp_listen = "127.0.0.1:4040"
+                quic_listen = "127.0.0.1:4040"
+                identity_key = "identity.key"
+                storage_dir = "storage"
+                {key_update_limit}
+                "#
+            ))
+            .unwrap();
+            assert!(config.server_config().is_err());
+        }
+    }
+}